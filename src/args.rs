@@ -1,5 +1,7 @@
 use clap::{crate_version, Parser};
 
+use crate::{layout::LayoutShape, transform::MapTransform};
+
 #[derive(Parser, Debug)]
 #[command(name = "Random Gores Map Generator - Editor")]
 #[command(version = crate_version!())]
@@ -47,9 +49,13 @@ pub struct CLIArgs {
     /// select initial generation config
     pub gen_config_name: String,
 
-    /// select initial map config
+    /// select initial map config, use "random" for a procedurally generated layout
     pub map_config_name: String,
 
+    /// shape of the procedurally generated layout, if map config is "random"
+    #[arg(long, value_enum, default_value = "snake")]
+    pub layout_shape: LayoutShape,
+
     /// enable fixed seed
     #[arg(short = 's', long = "seed")]
     pub fixed_seed: Option<u64>,
@@ -57,4 +63,41 @@ pub struct CLIArgs {
     /// The maximum amount of generation steps before generation stops
     #[arg(long, default_value = "200000")]
     pub max_gen_steps: usize,
+
+    /// generation config the difficulty should be interpolated towards along the map
+    #[arg(long)]
+    pub end_gen_config_name: Option<String>,
+
+    /// crop unused map border before export, so that only this many blocks remain
+    #[arg(long)]
+    pub crop_margin: Option<usize>,
+
+    /// abort generation after this many seconds
+    #[arg(long)]
+    pub timeout: Option<f32>,
+
+    /// check that a tee can move from spawn to finish, using a simplified movement model
+    #[arg(long)]
+    pub validate_movement: bool,
+
+    /// print metrics and estimated difficulty of the generated map
+    #[arg(long)]
+    pub metrics: bool,
+
+    /// json file with difficulty weights for the estimated difficulty, see
+    /// difficulty_calibration
+    #[arg(long)]
+    pub difficulty_weights: Option<String>,
+
+    /// write the route of the generated map (spawn, finish, main path, ...) to this json file
+    #[arg(long)]
+    pub report: Option<String>,
+
+    /// transform that is applied to the map before export
+    #[arg(long, value_enum, default_value = "random")]
+    pub transform: MapTransform,
+
+    /// how many times a failed generation is retried with a new seed
+    #[arg(long, default_value = "0")]
+    pub retries: usize,
 }
@@ -0,0 +1,338 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::BTreeMap,
+    fmt, fs,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocator that keeps track of the currently allocated and the peak amount of bytes. Only
+/// used if registered as #[global_allocator], as done by the benchmark.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            CountingAllocator::add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            CountingAllocator::add(new_size);
+        }
+        new_ptr
+    }
+}
+
+impl CountingAllocator {
+    fn add(size: usize) {
+        let allocated = ALLOCATED_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_ALLOCATED_BYTES.fetch_max(allocated, Ordering::Relaxed);
+    }
+
+    pub fn allocated_bytes() -> usize {
+        ALLOCATED_BYTES.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_allocated_bytes() -> usize {
+        PEAK_ALLOCATED_BYTES.load(Ordering::Relaxed)
+    }
+
+    /// restart peak tracking at the currently allocated amount of bytes
+    pub fn reset_peak() {
+        PEAK_ALLOCATED_BYTES.store(CountingAllocator::allocated_bytes(), Ordering::Relaxed);
+    }
+}
+
+/// measurements of a single successful generation
+#[derive(Debug, Clone)]
+pub struct BenchmarkSample {
+    pub duration: Duration,
+
+    /// duration of each stage, stages may occur multiple times
+    pub stage_timings: Vec<(&'static str, Duration)>,
+
+    /// additional bytes that were allocated at most during the generation. Allocations are
+    /// counted for the entire process, so this is only measured without parallel generations.
+    pub peak_memory: Option<usize>,
+}
+
+/// Benchmark results of one generation preset on one map preset. Times only include
+/// successful generations.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchmarkEntry {
+    pub gen_config: String,
+    pub map_config: String,
+    pub seed_count: usize,
+    pub success_count: usize,
+    pub error_count: usize,
+    pub panic_count: usize,
+    pub success_rate: f32,
+
+    /// share of seeds that failed with an error or panic
+    pub failure_rate: f32,
+
+    /// amount of each error message
+    pub errors: BTreeMap<String, usize>,
+
+    /// amount of each panic message, including its location
+    #[serde(default)]
+    pub panics: BTreeMap<String, usize>,
+
+    pub avg_time_ms: Option<f32>,
+    pub p50_time_ms: Option<f32>,
+    pub p95_time_ms: Option<f32>,
+
+    /// average time per map of each stage, in order of execution. Passes of the same stage
+    /// are summed up.
+    #[serde(default)]
+    pub stage_times_ms: Vec<(String, f32)>,
+
+    #[serde(default)]
+    pub avg_peak_memory_mb: Option<f32>,
+
+    #[serde(default)]
+    pub max_peak_memory_mb: Option<f32>,
+}
+
+impl BenchmarkEntry {
+    pub fn new(
+        gen_config: &str,
+        map_config: &str,
+        samples: &[BenchmarkSample],
+        errors: BTreeMap<String, usize>,
+        panics: BTreeMap<String, usize>,
+    ) -> BenchmarkEntry {
+        let error_count: usize = errors.values().sum();
+        let panic_count: usize = panics.values().sum();
+        let seed_count = samples.len() + error_count + panic_count;
+
+        let mut times_ms: Vec<f32> = samples
+            .iter()
+            .map(|sample| get_ms(&sample.duration))
+            .collect();
+        times_ms.sort_by(f32::total_cmp);
+
+        let mut stage_times_ms: Vec<(String, f32)> = Vec::new();
+        for (stage, duration) in samples
+            .iter()
+            .flat_map(|sample| sample.stage_timings.iter())
+        {
+            let time_ms = get_ms(duration) / samples.len() as f32;
+            match stage_times_ms.iter_mut().find(|(name, _)| name == stage) {
+                Some((_, total_ms)) => *total_ms += time_ms,
+                None => stage_times_ms.push((stage.to_string(), time_ms)),
+            }
+        }
+
+        let peak_memory_mb: Vec<f32> = samples
+            .iter()
+            .filter_map(|sample| sample.peak_memory)
+            .map(|peak_memory| peak_memory as f32 / (1024.0 * 1024.0))
+            .collect();
+
+        BenchmarkEntry {
+            gen_config: gen_config.to_string(),
+            map_config: map_config.to_string(),
+            seed_count,
+            success_count: samples.len(),
+            error_count,
+            panic_count,
+            success_rate: samples.len() as f32 / seed_count.max(1) as f32,
+            failure_rate: (error_count + panic_count) as f32 / seed_count.max(1) as f32,
+            errors,
+            panics,
+            avg_time_ms: (!times_ms.is_empty())
+                .then(|| times_ms.iter().sum::<f32>() / times_ms.len() as f32),
+            p50_time_ms: get_percentile(&times_ms, 0.5),
+            p95_time_ms: get_percentile(&times_ms, 0.95),
+            stage_times_ms,
+            avg_peak_memory_mb: (!peak_memory_mb.is_empty())
+                .then(|| peak_memory_mb.iter().sum::<f32>() / peak_memory_mb.len() as f32),
+            max_peak_memory_mb: peak_memory_mb.iter().cloned().reduce(f32::max),
+        }
+    }
+}
+
+fn get_ms(duration: &Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+/// nearest rank percentile of sorted values
+fn get_percentile(sorted_values: &[f32], percentile: f32) -> Option<f32> {
+    let rank = (percentile * sorted_values.len() as f32).ceil() as usize;
+    sorted_values.get(rank.saturating_sub(1)).copied()
+}
+
+/// allowed differences to a baseline before a benchmark entry counts as regression
+#[derive(Debug, Clone)]
+pub struct RegressionThresholds {
+    /// absolute increase of the failure rate
+    pub failure_rate: f32,
+
+    /// relative increase of the p50 and p95 times, e.g. 0.2 for 20% slower
+    pub time_ratio: f32,
+}
+
+/// benchmark entry that got worse compared to the baseline
+#[derive(Debug, Clone)]
+pub struct Regression {
+    pub gen_config: String,
+    pub map_config: String,
+    pub metric: &'static str,
+    pub baseline: f32,
+    pub current: f32,
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GEN {:<15} | LAYOUT {:<15} | {} {:.2} -> {:.2}",
+            self.gen_config, self.map_config, self.metric, self.baseline, self.current
+        )
+    }
+}
+
+/// results of an entire benchmark run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    pub max_generation_steps: usize,
+    pub entries: Vec<BenchmarkEntry>,
+}
+
+impl BenchmarkReport {
+    pub fn load(path: &str) -> Result<BenchmarkReport, &'static str> {
+        let serialized = fs::read_to_string(path).map_err(|_| "failed to read benchmark report")?;
+        serde_json::from_str(&serialized).map_err(|_| "failed to deserialize benchmark report")
+    }
+
+    pub fn save_json(&self, path: &str) -> Result<(), &'static str> {
+        let serialized = serde_json::to_string_pretty(self)
+            .map_err(|_| "failed to serialize benchmark report")?;
+        fs::write(path, serialized).map_err(|_| "failed to write benchmark report")
+    }
+
+    /// one line per entry, errors and panics are joined as count x message
+    pub fn to_csv(&self) -> String {
+        let format_value = |time: Option<f32>| time.map_or(String::new(), |t| format!("{:.3}", t));
+
+        let mut csv = "gen_config,map_config,seed_count,success_count,error_count,panic_count,\
+            success_rate,failure_rate,avg_time_ms,p50_time_ms,p95_time_ms,avg_peak_memory_mb,\
+            max_peak_memory_mb,stage_times_ms,errors\n"
+            .to_string();
+        for entry in self.entries.iter() {
+            let errors: Vec<String> = entry
+                .errors
+                .iter()
+                .map(|(error, count)| format!("{}x {}", count, error))
+                .chain(
+                    entry
+                        .panics
+                        .iter()
+                        .map(|(panic, count)| format!("{}x panic: {}", count, panic)),
+                )
+                .collect();
+            let stage_times: Vec<String> = entry
+                .stage_times_ms
+                .iter()
+                .map(|(stage, time)| format!("{}={:.3}", stage, time))
+                .collect();
+
+            csv += &format!(
+                "{},{},{},{},{},{},{:.4},{:.4},{},{},{},{},{},\"{}\",\"{}\"\n",
+                entry.gen_config,
+                entry.map_config,
+                entry.seed_count,
+                entry.success_count,
+                entry.error_count,
+                entry.panic_count,
+                entry.success_rate,
+                entry.failure_rate,
+                format_value(entry.avg_time_ms),
+                format_value(entry.p50_time_ms),
+                format_value(entry.p95_time_ms),
+                format_value(entry.avg_peak_memory_mb),
+                format_value(entry.max_peak_memory_mb),
+                stage_times.join("; "),
+                errors.join("; ").replace('"', "\"\""),
+            );
+        }
+
+        csv
+    }
+
+    pub fn save_csv(&self, path: &str) -> Result<(), &'static str> {
+        fs::write(path, self.to_csv()).map_err(|_| "failed to write benchmark csv")
+    }
+
+    /// Compares all entries to the entries of the same presets in the baseline. Entries that are
+    /// not part of the baseline are ignored.
+    pub fn compare(
+        &self,
+        baseline: &BenchmarkReport,
+        thresholds: &RegressionThresholds,
+    ) -> Vec<Regression> {
+        let mut regressions = Vec::new();
+
+        for entry in self.entries.iter() {
+            let Some(base) = baseline
+                .entries
+                .iter()
+                .find(|b| b.gen_config == entry.gen_config && b.map_config == entry.map_config)
+            else {
+                continue;
+            };
+
+            let mut check = |metric: &'static str, baseline: f32, current: f32, limit: f32| {
+                if current > limit {
+                    regressions.push(Regression {
+                        gen_config: entry.gen_config.clone(),
+                        map_config: entry.map_config.clone(),
+                        metric,
+                        baseline,
+                        current,
+                    });
+                }
+            };
+
+            check(
+                "FAILURE_RATE",
+                base.failure_rate,
+                entry.failure_rate,
+                base.failure_rate + thresholds.failure_rate,
+            );
+
+            for (metric, base_time, time) in [
+                ("P50_TIME_MS", base.p50_time_ms, entry.p50_time_ms),
+                ("P95_TIME_MS", base.p95_time_ms, entry.p95_time_ms),
+            ] {
+                if let (Some(base_time), Some(time)) = (base_time, time) {
+                    check(
+                        metric,
+                        base_time,
+                        time,
+                        base_time * (1.0 + thresholds.time_ratio),
+                    );
+                }
+            }
+        }
+
+        regressions
+    }
+}
@@ -1,14 +1,28 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::env;
 use std::panic;
-use std::time::{Duration, Instant};
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Instant;
 
 use clap::Parser;
 use indicatif::{ProgressBar, ProgressStyle};
 use seed_gen::cli::{SeedIter, Seeds};
 
+use gores_mapgen::benchmark::{
+    BenchmarkEntry, BenchmarkReport, BenchmarkSample, CountingAllocator, RegressionThresholds,
+};
+use gores_mapgen::builder::GeneratorBuilder;
 use gores_mapgen::config::{get_filtered_configs, GenerationConfig, MapConfig, ThemeConfig};
-use gores_mapgen::generator::Generator;
 use gores_mapgen::random::Seed;
+use gores_mapgen::retry::FailureKind;
+use gores_mapgen::twmap_export::TwExport;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
 
 #[derive(Parser, Debug)]
 /// Benchmarks map generation with the specified options. Default is seeds 0 to 99.
@@ -25,6 +39,10 @@ pub struct Args {
     #[arg(short, long, value_delimiter = ',')]
     pub map_preset_names: Option<Vec<String>>,
 
+    /// Amount of seeds that are generated in parallel. Peak memory is only measured if 1
+    #[arg(short = 'j', long, default_value = "1")]
+    pub threads: usize,
+
     /// Specify which seed/seeds to use. Default 0 to 99
     #[command(subcommand)]
     pub seeds: Option<Seeds>,
@@ -32,6 +50,34 @@ pub struct Args {
     /// Print detailed error summary
     #[arg(short = 'e')]
     pub error_summary: bool,
+
+    /// Print average time and share of each generation stage
+    #[arg(short = 't')]
+    pub stage_summary: bool,
+
+    /// Also benchmark export preprocessing and map export, maps are written to a temp file
+    #[arg(long)]
+    pub export: bool,
+
+    /// Write results as json, which can be used as baseline for --compare
+    #[arg(long)]
+    pub json: Option<String>,
+
+    /// Write results as csv
+    #[arg(long)]
+    pub csv: Option<String>,
+
+    /// Compare results against a baseline json and exit with an error on regressions
+    #[arg(long)]
+    pub compare: Option<String>,
+
+    /// Allowed absolute increase of the failure rate compared to the baseline
+    #[arg(long, default_value = "0.02")]
+    pub max_failure_rate_increase: f32,
+
+    /// Allowed relative increase of p50/p95 times compared to the baseline
+    #[arg(long, default_value = "0.25")]
+    pub max_time_increase: f32,
 }
 
 /// derive seed iter from cli args, use default 0 to 99 if non is specified
@@ -46,12 +92,122 @@ fn get_seed_iter(args: &Args) -> SeedIter {
         .iter()
 }
 
+thread_local! {
+    /// message and location of the last panic on this thread, set by the panic hook
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// outcome of generating a single seed
+enum SeedResult {
+    Success(BenchmarkSample),
+    Error(String),
+    Panic(String),
+}
+
+fn benchmark_seed(
+    args: &Args,
+    gen_config: &GenerationConfig,
+    map_config: &MapConfig,
+    seed: u64,
+    export_path: &PathBuf,
+) -> SeedResult {
+    let measure_memory = args.threads <= 1;
+    LAST_PANIC.with(|last_panic| last_panic.borrow_mut().take());
+
+    CountingAllocator::reset_peak();
+    let start_memory = CountingAllocator::allocated_bytes();
+    let start_time = Instant::now();
+    let generation_result = GeneratorBuilder::new()
+        .with_gen_config(gen_config.clone())
+        .with_map_config(map_config.clone())
+        .with_theme_config(ThemeConfig::default())
+        .with_seed(Seed::from_u64(seed))
+        .with_max_steps(args.max_generation_steps)
+        .with_export_preprocess(args.export)
+        .generate();
+
+    match generation_result {
+        // map was generated successfully
+        Ok(result) => {
+            let mut stage_timings: Vec<(&'static str, _)> = result
+                .timings
+                .iter()
+                .map(|(stage, duration)| (stage.name(), *duration))
+                .collect();
+
+            if args.export {
+                let export_start = Instant::now();
+                TwExport::export(&result.map, export_path);
+                stage_timings.push(("export", export_start.elapsed()));
+            }
+
+            SeedResult::Success(BenchmarkSample {
+                duration: start_time.elapsed(),
+                stage_timings,
+                peak_memory: measure_memory.then(|| {
+                    CountingAllocator::peak_allocated_bytes().saturating_sub(start_memory)
+                }),
+            })
+        }
+        // map generation panic, the message was stored by the panic hook
+        Err(failure) if failure.last_failure().kind == FailureKind::Panic => SeedResult::Panic(
+            LAST_PANIC
+                .with(|last_panic| last_panic.borrow_mut().take())
+                .unwrap_or(failure.error().to_string()),
+        ),
+        // no panic, but map generation failed
+        Err(failure) => SeedResult::Error(failure.error().to_string()),
+    }
+}
+
+/// Generates all seeds using the given amount of threads. Results are ordered like the seeds.
+fn benchmark_seeds(
+    args: &Args,
+    gen_config: &GenerationConfig,
+    map_config: &MapConfig,
+    seeds: &[u64],
+    pb: &ProgressBar,
+) -> Vec<SeedResult> {
+    let next_index = AtomicUsize::new(0);
+
+    let mut results: Vec<(usize, SeedResult)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..args.threads.max(1))
+            .map(|worker| {
+                let next_index = &next_index;
+                let export_path =
+                    env::temp_dir().join(format!("gores_mapgen_benchmark_{}.map", worker));
+
+                scope.spawn(move || {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::Relaxed);
+                        let Some(seed) = seeds.get(index) else {
+                            break;
+                        };
+                        let result =
+                            benchmark_seed(args, gen_config, map_config, *seed, &export_path);
+                        results.push((index, result));
+                        pb.inc(1);
+                    }
+                    results
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
 fn main() {
     let args = Args::parse();
 
-    // determine seed count
-    // TODO: iterates over one entire seed_gen once.. -> implement size_hint()?
-    let seed_count = get_seed_iter(&args).count();
+    let seeds: Vec<u64> = get_seed_iter(&args).collect();
 
     let init_map_configs = match &args.map_preset_names {
         Some(preset_names) => get_filtered_configs(&MapConfig::get_all_configs(), preset_names),
@@ -65,8 +221,32 @@ fn main() {
         None => GenerationConfig::get_all_configs(),
     };
 
-    // disable panic hook so they no longer get printed
-    panic::set_hook(Box::new(|_info| {}));
+    // load baseline before benchmarking, so an invalid path fails early
+    let baseline = args
+        .compare
+        .as_ref()
+        .map(|path| BenchmarkReport::load(path).unwrap_or_else(|err| panic!("{}: {}", err, path)));
+
+    // panics are not printed, but their message and location is kept for the error summary
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or("unknown panic payload".to_string());
+        let location = info
+            .location()
+            .map(|location| format!(" at {}:{}", location.file(), location.line()))
+            .unwrap_or_default();
+
+        LAST_PANIC.with(|last_panic| *last_panic.borrow_mut() = Some(message + &location));
+    }));
+
+    let mut report = BenchmarkReport {
+        max_generation_steps: args.max_generation_steps,
+        entries: Vec::new(),
+    };
 
     for map_config in init_map_configs.iter() {
         println!(
@@ -76,13 +256,7 @@ fn main() {
         );
 
         for gen_config in init_gen_configs.iter() {
-            let mut elapsed = Duration::ZERO;
-            let mut panic_count = 0;
-            let mut error_count = 0;
-            let mut valid_count = 0;
-            let mut error_summary: HashMap<String, usize> = HashMap::new();
-
-            let pb = ProgressBar::new(seed_count as u64);
+            let pb = ProgressBar::new(seeds.len() as u64);
             pb.set_style(
                 ProgressStyle::with_template(
                     "[{elapsed_precise}] {bar:56.cyan/blue} {pos:>7}/{len:7} {msg}",
@@ -90,59 +264,94 @@ fn main() {
                 .unwrap()
                 .progress_chars("##-"),
             );
-            for seed in get_seed_iter(&args) {
-                let seed = Seed::from_u64(seed);
-                let start_time = Instant::now();
-                let generation_result = panic::catch_unwind(|| {
-                    Generator::generate_map(
-                        args.max_generation_steps,
-                        &seed,
-                        gen_config,
-                        map_config,
-                        &ThemeConfig::default(),
-                        false,
-                    )
-                });
-
-                match generation_result {
-                    // map was generated successfully
-                    Ok(Ok(_map)) => {
-                        elapsed += start_time.elapsed();
-                        valid_count += 1;
-                    }
-                    // no panic, but map generation failed
-                    Ok(Err(_generation_error)) => {
-                        error_count += 1;
-                        let error_message = format!("{:?}", _generation_error);
-                        *error_summary.entry(error_message).or_insert(0) += 1;
-                    }
-                    // map generation panic
-                    Err(_panic_info) => {
-                        panic_count += 1;
-                    }
+            let results = benchmark_seeds(&args, gen_config, map_config, &seeds, &pb);
+            pb.finish_and_clear();
+
+            let mut samples = Vec::new();
+            let mut errors: BTreeMap<String, usize> = BTreeMap::new();
+            let mut panics: BTreeMap<String, usize> = BTreeMap::new();
+            for result in results {
+                match result {
+                    SeedResult::Success(sample) => samples.push(sample),
+                    SeedResult::Error(error) => *errors.entry(error).or_insert(0) += 1,
+                    SeedResult::Panic(panic) => *panics.entry(panic).or_insert(0) += 1,
                 }
-                pb.inc(1);
             }
-            pb.finish_and_clear();
 
-            let avg_elapsed_text = elapsed
-                .checked_div(valid_count)
-                .map(|v| format!("{} ms", v.as_millis()))
+            let entry =
+                BenchmarkEntry::new(&gen_config.name, &map_config.name, &samples, errors, panics);
+
+            let avg_elapsed_text = entry
+                .avg_time_ms
+                .map(|v| format!("{:.0} ms", v))
                 .unwrap_or("?".to_string());
-            let error_rate = (error_count as f32) / (seed_count as f32);
-            let panic_rate = (panic_count as f32) / (seed_count as f32);
+            let error_rate = entry.error_count as f32 / entry.seed_count as f32;
+            let panic_rate = entry.panic_count as f32 / entry.seed_count as f32;
 
             println!(
                 "GEN {:<15} | AVG_TIME={:<12} | ERROR_RATE={:<4.2} | PANIC_RATE={:<4.2}",
                 gen_config.name, avg_elapsed_text, error_rate, panic_rate
             );
 
-            if args.error_summary && !error_summary.is_empty() {
+            if args.stage_summary {
+                let total_ms: f32 = entry.stage_times_ms.iter().map(|(_, time)| time).sum();
+                println!("  Stage summary:");
+                for (stage, time) in entry.stage_times_ms.iter() {
+                    println!(
+                        "    {:<28} {:>9.2} ms {:>5.1}%",
+                        stage,
+                        time,
+                        100.0 * time / total_ms
+                    );
+                }
+                if let (Some(avg), Some(max)) = (entry.avg_peak_memory_mb, entry.max_peak_memory_mb)
+                {
+                    println!("    peak memory: avg {:.1} MB, max {:.1} MB", avg, max);
+                }
+            }
+
+            if args.error_summary && (!entry.errors.is_empty() || !entry.panics.is_empty()) {
                 println!("  Error summary:");
-                for (err, count) in error_summary.iter() {
-                    println!("    {}x {}", count, err);
+                for (err, count) in entry.errors.iter() {
+                    println!("    {}x {:?}", count, err);
+                }
+                for (panic, count) in entry.panics.iter() {
+                    println!("    {}x panic: {:?}", count, panic);
                 }
             }
+
+            report.entries.push(entry);
+        }
+    }
+
+    if let Some(path) = &args.json {
+        report
+            .save_json(path)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+    if let Some(path) = &args.csv {
+        report
+            .save_csv(path)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    if let Some(baseline) = baseline {
+        let regressions = report.compare(
+            &baseline,
+            &RegressionThresholds {
+                failure_rate: args.max_failure_rate_increase,
+                time_ratio: args.max_time_increase,
+            },
+        );
+
+        if regressions.is_empty() {
+            println!("\nno regressions compared to baseline");
+        } else {
+            println!("\n{} regressions compared to baseline:", regressions.len());
+            for regression in regressions.iter() {
+                println!("  {}", regression);
+            }
+            exit(1);
         }
     }
 }
@@ -1,59 +1,137 @@
 use clap::Parser;
 use gores_mapgen::{
     args::CLIArgs,
-    config::{GenerationConfig, MapConfig, ThemeConfig},
-    generator::Generator,
+    builder::GeneratorBuilder,
+    difficulty::{DifficultyEstimate, DifficultyWeights},
+    layout::LayoutConfig,
+    metrics::MapMetrics,
+    pipeline::{PostPass, PostStage},
+    progress::CancellationToken,
     random::{Random, Seed},
+    retry::RetryPolicy,
 };
 use log::{info, warn};
 use simple_logger::SimpleLogger;
-use std::panic::{self};
+use std::time::Duration;
 
 fn main() {
     let args = CLIArgs::parse();
     SimpleLogger::new().init().unwrap();
 
-    let all_map_configs = MapConfig::get_all_configs();
-    let all_gen_configs = GenerationConfig::get_all_configs();
+    let seed = args.fixed_seed.unwrap_or(Random::get_u64_from_entropy());
 
-    let map_config = all_map_configs
-        .iter()
-        .find(|c| c.name == args.map_config_name)
-        .unwrap_or_else(|| panic!("map config '{}' not found", args.map_config_name));
+    let mut builder = GeneratorBuilder::new()
+        .with_gen_preset(&args.gen_config_name)
+        .unwrap_or_else(|_| panic!("gen config '{}' not found", args.gen_config_name))
+        .with_seed(Seed::from_u64(seed))
+        .with_max_steps(args.max_gen_steps)
+        .with_retry_policy(RetryPolicy::default().with_max_attempts(args.retries + 1))
+        .with_crop_margin(args.crop_margin)
+        .with_transform(args.transform)
+        .with_export_preprocess(true); // TODO: add CLIArg
 
-    let gen_config = all_gen_configs
-        .iter()
-        .find(|c| c.name == args.gen_config_name)
-        .unwrap_or_else(|| panic!("gen config '{}' not found", args.map_config_name));
+    if args.validate_movement {
+        PostPass::set_stage_enabled(
+            &mut builder.gen_config.post_processing,
+            PostStage::MovementValidation,
+            true,
+        );
+    }
 
-    let seed = args.fixed_seed.unwrap_or(Random::get_u64_from_entropy());
+    if let Some(end_config_name) = &args.end_gen_config_name {
+        builder = builder
+            .with_curve_end_preset(end_config_name)
+            .unwrap_or_else(|_| panic!("gen config '{}' not found", end_config_name));
+    }
+
+    // random layouts are derived from the same seed, so maps stay reproducible
+    if args.map_config_name == "random" {
+        builder = builder.with_layout(Some(LayoutConfig {
+            shape: args.layout_shape,
+            ..LayoutConfig::default()
+        }));
+    } else {
+        builder = builder
+            .with_map_preset(&args.map_config_name)
+            .unwrap_or_else(|_| panic!("map config '{}' not found", args.map_config_name));
+    }
+
+    let weights = match &args.difficulty_weights {
+        Some(path) => DifficultyWeights::load(path).unwrap_or_else(|err| panic!("{}", err)),
+        None => DifficultyWeights::default(),
+    };
+
+    let cancel = args
+        .timeout
+        .map(|timeout| CancellationToken::with_timeout(Duration::from_secs_f32(timeout)))
+        .unwrap_or_default();
 
     // disable panic hook so they no longer get printed
     // panic::set_hook(Box::new(|_info| {}));
 
-    let generation_result = panic::catch_unwind(|| {
-        Generator::generate_map(
-            args.max_gen_steps,
-            &Seed::from_u64(seed),
-            gen_config,
-            map_config,
-            &ThemeConfig::default(),
-            true, // TODO: add CLIArg
-        )
-    });
+    let mut last_stage = None;
+    let generation_result = builder.generate_observed(
+        &mut |progress| {
+            if last_stage != Some((progress.attempt, progress.stage)) {
+                info!(
+                    "{} (attempt {}, step {}, {:.2?})",
+                    progress.stage.name(),
+                    progress.attempt,
+                    progress.steps,
+                    progress.elapsed
+                );
+                last_stage = Some((progress.attempt, progress.stage));
+            }
+        },
+        &cancel,
+    );
+
+    let attempts = match &generation_result {
+        Ok(result) => &result.attempts,
+        Err(failure) => &failure.attempts,
+    };
+    for attempt in attempts.iter() {
+        if let Some(failure) = &attempt.failure {
+            warn!(
+                "attempt {} (seed {}) failed: {:?} {}",
+                attempt.index, attempt.seed.seed_u64, failure.kind, failure.error
+            );
+        }
+    }
 
     match generation_result {
         // map was generated successfully
-        Ok(Ok(_map)) => {
-            info!("generation success!");
-        }
-        // no panic, but map generation failed
-        Ok(Err(generation_error)) => {
-            warn!("generation failed: {}", generation_error)
+        Ok(result) => {
+            info!(
+                "generation success! (seed {}, {} attempts)",
+                result.seed.seed_u64,
+                result.attempts.len()
+            );
+
+            if args.metrics {
+                match MapMetrics::new(&result.map, &result.report) {
+                    Ok(metrics) => {
+                        info!("map metrics:\n{}", metrics);
+
+                        let estimate = DifficultyEstimate::new(&metrics, &weights);
+                        info!(
+                            "estimated difficulty: {:.2} ({:.2} points)",
+                            estimate.difficulty, estimate.points
+                        );
+                    }
+                    Err(err) => warn!("failed to determine map metrics: {}", err),
+                }
+            }
+
+            if let Some(report_path) = &args.report {
+                match result.report.save(report_path) {
+                    Ok(()) => info!("saved report to {}", report_path),
+                    Err(err) => warn!("{}", err),
+                }
+            }
         }
-        // map generation panic
-        Err(panic_info) => {
-            warn!("generation panicked: {:?}", panic_info)
+        Err(failure) => {
+            warn!("generation failed: {}", failure.error())
         }
     }
 }
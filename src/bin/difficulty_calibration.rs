@@ -0,0 +1,111 @@
+use std::panic;
+
+use clap::Parser;
+
+use gores_mapgen::builder::GeneratorBuilder;
+use gores_mapgen::config::{get_config_points, GenerationConfig, MapConfig};
+use gores_mapgen::difficulty::{
+    load_difficulty_labels, DifficultyEstimate, DifficultyFeatures, DifficultyLabel,
+    DifficultyWeights,
+};
+use gores_mapgen::metrics::MapMetrics;
+use gores_mapgen::random::Seed;
+
+#[derive(Parser, Debug)]
+/// Fits the difficulty weights against labeled maps
+pub struct Args {
+    /// json file with labeled maps, see DifficultyLabel
+    #[arg(short, long)]
+    pub labels: String,
+
+    /// The maximum amount of generation steps before generation stops
+    #[arg(short = 's', long, default_value = "200000")]
+    pub max_generation_steps: usize,
+
+    /// write the fitted weights to this json file
+    #[arg(short, long)]
+    pub output: Option<String>,
+}
+
+fn get_metrics(label: &DifficultyLabel, max_steps: usize) -> Result<MapMetrics, &'static str> {
+    let result = GeneratorBuilder::new()
+        .with_gen_preset(&label.gen_config)?
+        .with_map_preset(&label.map_config)?
+        .with_seed(Seed::from_u64(label.seed))
+        .with_max_steps(max_steps)
+        .generate()
+        .map_err(|failure| failure.error())?;
+
+    MapMetrics::new(&result.map, &result.report)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    // disable panic hook so they no longer get printed
+    panic::set_hook(Box::new(|_info| {}));
+
+    let labels = load_difficulty_labels(&args.labels).unwrap_or_else(|err| panic!("{}", err));
+
+    // failed generations are skipped, as there is no map to label
+    let mut samples: Vec<(&DifficultyLabel, MapMetrics)> = Vec::new();
+    for label in labels.iter() {
+        match get_metrics(label, args.max_generation_steps) {
+            Ok(metrics) => samples.push((label, metrics)),
+            Err(err) => println!(
+                "skipping {} {} seed={}: {}",
+                label.gen_config, label.map_config, label.seed, err
+            ),
+        }
+    }
+
+    let weights = DifficultyWeights::fit(
+        &samples
+            .iter()
+            .map(|(label, metrics)| (DifficultyFeatures::new(metrics), label.difficulty))
+            .collect::<Vec<_>>(),
+    )
+    .unwrap_or_else(|err| panic!("{}", err));
+
+    println!(
+        "\n{:<10} {:<15} {:<6} | {:>6} {:>9} | {:>7} {:>10}",
+        "GEN", "MAP", "SEED", "LABEL", "ESTIMATE", "POINTS", "CFG POINTS"
+    );
+    let gen_configs = GenerationConfig::get_all_configs();
+    let map_configs = MapConfig::get_all_configs();
+    let mut total_error = 0.0;
+    for (label, metrics) in samples.iter() {
+        let estimate = DifficultyEstimate::new(metrics, &weights);
+        total_error += (estimate.difficulty - label.difficulty).abs();
+
+        // labels may use configs that are not bundled
+        let gen_config = gen_configs.iter().find(|c| c.name == label.gen_config);
+        let map_config = map_configs.iter().find(|c| c.name == label.map_config);
+        let config_points = gen_config
+            .zip(map_config)
+            .map(|(gen_config, map_config)| get_config_points(gen_config, map_config));
+
+        println!(
+            "{:<10} {:<15} {:<6} | {:>6.2} {:>9.2} | {:>7.2} {:>10}",
+            label.gen_config,
+            label.map_config,
+            label.seed,
+            label.difficulty,
+            estimate.difficulty,
+            estimate.points,
+            config_points.map_or("-".to_string(), |points| format!("{:.2}", points)),
+        );
+    }
+
+    println!(
+        "\nmean absolute error: {:.3} ({} samples)",
+        total_error / samples.len() as f32,
+        samples.len()
+    );
+    println!("{:#?}", weights);
+
+    if let Some(output) = &args.output {
+        weights.save(output).unwrap_or_else(|err| panic!("{}", err));
+        println!("saved weights to {}", output);
+    }
+}
@@ -12,7 +12,6 @@ use gores_mapgen::{
 use macroquad::{color::*, miniquad, window::*};
 use miniquad::conf::{Conf, Platform};
 use simple_logger::SimpleLogger;
-use std::panic::{self, AssertUnwindSafe};
 
 const DISABLE_VSYNC: bool = true;
 
@@ -60,59 +59,11 @@ async fn main() {
             false => editor.steps_per_frame,
         };
 
-        for _ in 0..generation_steps {
-            if editor.is_paused() || editor.gen.walker.finished {
-                break;
-            }
-
-            editor
-                .gen
-                .step(&editor.gen_config, true, &mut editor.debug_layers)
-                .unwrap_or_else(|err| {
-                    println!("Walker Step Failed: {:}", err);
-                    editor.set_setup();
-
-                    if editor.retry_on_failure {
-                        editor.set_playing();
-                    }
-                });
-
-            // walker did a step using SingleStep -> now pause
-            if editor.is_single_setp() {
-                editor.set_stopped();
-            }
-        }
+        editor.step_walker(generation_steps);
 
-        // this is called ONCE after map was generated
-        // TODO: handling successfull generation via 'setup' state is kinda stupid, i should
-        // just add a new state variable for this, in the generator?
-        if editor.gen.walker.finished && !editor.is_setup() {
-            // kinda crappy, but ensure that even a panic doesnt crash the program
-            let _ = panic::catch_unwind(AssertUnwindSafe(|| {
-                editor
-                    .gen
-                    .perform_all_post_processing(
-                        &editor.gen_config,
-                        &editor.thm_config,
-                        &mut editor.debug_layers,
-                        editor.verbose_post_process,
-                    )
-                    .unwrap_or_else(|err| {
-                        println!("Post Processing Failed: {:}", err);
-                    });
-
-                if editor.export_preprocess {
-                    editor.gen.export_preprocess(
-                        &editor.thm_config,
-                        &mut editor.debug_layers,
-                        editor.verbose_post_process,
-                    );
-                }
-            }));
-
-            // switch into setup mode for next map
-            editor.set_setup();
-        }
+        // post processing is performed once the walker is finished. When single stepping, only
+        // one post processing stage is performed per step.
+        editor.step_post_processing();
 
         editor.define_egui();
         editor.update_cam();
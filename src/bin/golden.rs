@@ -0,0 +1,79 @@
+use std::{panic, process::exit};
+
+use clap::Parser;
+
+use gores_mapgen::golden::{
+    generate_golden_cases, load_golden_cases, save_golden_cases, GOLDEN_HASHES_PATH,
+};
+
+#[derive(Parser, Debug)]
+/// Checks that fixed generation inputs still result in the golden map hashes, and that the
+/// editor generation results in the same maps as the one-shot generation.
+pub struct Args {
+    /// path of the golden hashes file
+    #[arg(short, long, default_value = GOLDEN_HASHES_PATH)]
+    pub path: String,
+
+    /// regenerate and overwrite the golden hashes, only do this for intended changes!
+    #[arg(long)]
+    pub bless: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    // disable panic hook so they no longer get printed
+    panic::set_hook(Box::new(|_info| {}));
+
+    if args.bless {
+        let cases = generate_golden_cases();
+        save_golden_cases(&args.path, &cases).unwrap_or_else(|err| panic!("{}", err));
+        println!("blessed {} golden hashes", cases.len());
+        return;
+    }
+
+    let cases = load_golden_cases(&args.path).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+
+    let mut failed_count = 0;
+    for case in cases.iter() {
+        let result = case.check();
+        let status = if !result.is_deterministic() {
+            "EDITOR MISMATCH"
+        } else if !result.matches_golden() {
+            "GOLDEN MISMATCH"
+        } else {
+            "ok"
+        };
+
+        println!(
+            "{:<10} {:<15} seed={:<4} expected={:<18} one_shot={:<18} editor={:<18} {}",
+            case.gen_config,
+            case.map_config,
+            case.seed,
+            format_hash(case.hash),
+            format_hash(result.one_shot_hash),
+            format_hash(result.editor_hash),
+            status
+        );
+
+        if !result.matches_golden() {
+            failed_count += 1;
+        }
+    }
+
+    println!(
+        "{}/{} cases passed",
+        cases.len() - failed_count,
+        cases.len()
+    );
+    if failed_count > 0 {
+        exit(1);
+    }
+}
+
+fn format_hash(hash: Option<u64>) -> String {
+    hash.map_or("failed".to_string(), |hash| format!("{:016x}", hash))
+}
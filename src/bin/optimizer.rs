@@ -0,0 +1,240 @@
+use std::panic;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use clap::{Parser, ValueEnum};
+
+use gores_mapgen::config::{get_filtered_configs, GenerationConfig, MapConfig};
+use gores_mapgen::random::{Random, Seed};
+use gores_mapgen::tuning::{Evaluation, MetricTarget, TargetMetric, TuningConfig};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Strategy {
+    /// sample all parameters uniformly within their bounds
+    Random,
+
+    /// mutate the best candidates of the previous iteration
+    Evolution,
+}
+
+#[derive(Parser, Debug)]
+/// Tunes a generation preset by searching its parameters within the bounds of the tuning
+/// config. The best candidates are written as new preset files.
+pub struct Args {
+    /// Generation preset that is tuned
+    #[arg(short, long, default_value = "main")]
+    pub gen_preset_name: String,
+
+    /// Map presets to evaluate candidates on (comma-separated values)
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "small_s_tight,climb"
+    )]
+    pub map_preset_names: Vec<String>,
+
+    /// Candidates are evaluated on seeds 0 to n-1 of each map preset
+    #[arg(short = 'n', long, default_value = "10")]
+    pub seed_count: u64,
+
+    /// The maximum amount of generation steps before generation stops
+    #[arg(short = 's', long, default_value = "200000")]
+    pub max_generation_steps: usize,
+
+    #[arg(long, value_enum, default_value = "evolution")]
+    pub strategy: Strategy,
+
+    /// Amount of candidates per iteration and of kept candidates for evolution
+    #[arg(short, long, default_value = "8")]
+    pub population: usize,
+
+    #[arg(short, long, default_value = "10")]
+    pub iterations: usize,
+
+    /// Json file with parameter bounds, metric targets and score weights, see TuningConfig
+    #[arg(long)]
+    pub tuning: Option<String>,
+
+    /// Target mean corridor width, added to the targets of the tuning config
+    #[arg(long)]
+    pub corridor_width: Option<f32>,
+
+    /// Target mean platform gap, added to the targets of the tuning config
+    #[arg(long)]
+    pub platform_gap: Option<f32>,
+
+    /// Target estimated difficulty, added to the targets of the tuning config
+    #[arg(long)]
+    pub difficulty: Option<f32>,
+
+    /// Seed of the search itself
+    #[arg(long, default_value = "0")]
+    pub search_seed: u64,
+
+    /// Amount of candidates that are evaluated in parallel
+    #[arg(short = 'j', long, default_value = "1")]
+    pub threads: usize,
+
+    /// Amount of best candidates that are written as presets
+    #[arg(short, long, default_value = "3")]
+    pub keep: usize,
+
+    /// Directory the tuned presets are written to
+    #[arg(short, long, default_value = ".")]
+    pub output_dir: String,
+}
+
+/// Evaluates all candidates using the given amount of threads. Results are ordered like the
+/// candidates.
+fn evaluate_all(
+    args: &Args,
+    tuning: &TuningConfig,
+    candidates: Vec<GenerationConfig>,
+    map_configs: &[MapConfig],
+    seeds: &[u64],
+) -> Vec<(GenerationConfig, Evaluation)> {
+    let next_index = AtomicUsize::new(0);
+
+    let mut evaluations: Vec<(usize, Evaluation)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..args.threads.max(1))
+            .map(|_| {
+                let (next_index, candidates) = (&next_index, &candidates);
+
+                scope.spawn(move || {
+                    let mut evaluations = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::Relaxed);
+                        let Some(candidate) = candidates.get(index) else {
+                            break;
+                        };
+                        let evaluation = tuning.evaluate(
+                            candidate,
+                            map_configs,
+                            seeds,
+                            args.max_generation_steps,
+                        );
+                        evaluations.push((index, evaluation));
+                    }
+                    evaluations
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    evaluations.sort_by_key(|(index, _)| *index);
+    candidates
+        .into_iter()
+        .zip(evaluations)
+        .map(|(candidate, (_, evaluation))| (candidate, evaluation))
+        .collect()
+}
+
+fn print_evaluation(label: &str, evaluation: &Evaluation, tuning: &TuningConfig) {
+    let metrics: Vec<String> = tuning
+        .targets
+        .iter()
+        .zip(evaluation.metrics.iter())
+        .map(|(target, value)| {
+            let value = value.map_or("?".to_string(), |value| format!("{:.2}", value));
+            format!("{:?}={}", target.metric, value)
+        })
+        .collect();
+
+    println!(
+        "{:<12} | SCORE={:<7.3} | SUCCESS={:<4.2} | AVG_TIME={:<10} | {}",
+        label,
+        evaluation.score,
+        evaluation.success_rate,
+        evaluation
+            .avg_time_ms
+            .map_or("?".to_string(), |time| format!("{:.0} ms", time)),
+        metrics.join(" ")
+    );
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let mut tuning = match &args.tuning {
+        Some(path) => TuningConfig::load(path).unwrap_or_else(|err| panic!("{}", err)),
+        None => TuningConfig::default(),
+    };
+    for (metric, target) in [
+        (TargetMetric::MeanCorridorWidth, args.corridor_width),
+        (TargetMetric::MeanPlatformGap, args.platform_gap),
+        (TargetMetric::Difficulty, args.difficulty),
+    ] {
+        if let Some(target) = target {
+            tuning.targets.push(MetricTarget {
+                metric,
+                target,
+                weight: 1.0,
+            });
+        }
+    }
+    tuning.validate().unwrap_or_else(|err| panic!("{}", err));
+
+    let base = GenerationConfig::get_all_configs()
+        .into_iter()
+        .find(|config| config.name == args.gen_preset_name)
+        .unwrap_or_else(|| panic!("gen config '{}' not found", args.gen_preset_name));
+    let map_configs = get_filtered_configs(&MapConfig::get_all_configs(), &args.map_preset_names);
+    let seeds: Vec<u64> = (0..args.seed_count).collect();
+    let mut rnd = Random::new(Seed::from_u64(args.search_seed), &base);
+
+    // disable panic hook so they no longer get printed
+    panic::set_hook(Box::new(|_info| {}));
+
+    let mut population = evaluate_all(&args, &tuning, vec![base.clone()], &map_configs, &seeds);
+    print_evaluation("base", &population[0].1, &tuning);
+
+    for iteration in 0..args.iterations {
+        let candidates: Vec<GenerationConfig> = (0..args.population)
+            .map(|_| match args.strategy {
+                Strategy::Random => tuning.sample(&base, &mut rnd),
+                Strategy::Evolution => {
+                    let parent = rnd.pick_from_slice(&population).0.clone();
+                    tuning.mutate(&parent, &mut rnd)
+                }
+            })
+            .collect();
+
+        population.extend(evaluate_all(
+            &args,
+            &tuning,
+            candidates,
+            &map_configs,
+            &seeds,
+        ));
+        population.sort_by(|a, b| b.1.score.total_cmp(&a.1.score));
+        population.truncate(args.population.max(args.keep));
+
+        print_evaluation(
+            &format!("iteration {}", iteration + 1),
+            &population[0].1,
+            &tuning,
+        );
+    }
+
+    println!();
+    for (rank, (config, evaluation)) in population.iter().take(args.keep).enumerate() {
+        let mut config = config.clone();
+        config.name = format!("{}_tuned_{}", base.name, rank + 1);
+        config.description = Some(format!(
+            "tuned from {} (score {:.3}, success rate {:.2})",
+            base.name, evaluation.score, evaluation.success_rate
+        ));
+
+        let path = Path::new(&args.output_dir).join(format!("{}.json", config.name));
+        config.save(path.to_str().unwrap());
+        print_evaluation(&config.name, evaluation, &tuning);
+        println!("  saved to {}", path.display());
+    }
+}
@@ -0,0 +1,426 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    time::Instant,
+};
+
+use crate::{
+    config::{DifficultyCurve, GenerationConfig, MapConfig, ThemeConfig},
+    debug::DebugLayers,
+    generator::Generator,
+    layout::LayoutConfig,
+    map::Map,
+    pipeline::PipelineRunner,
+    progress::{
+        CancellationToken, GenerationStage, Progress, StageTimings, PROGRESS_STEP_INTERVAL,
+    },
+    random::Seed,
+    report::GenerationReport,
+    retry::{Attempt, AttemptFailure, FailureKind, GenerationFailure, RetryPolicy},
+    transform::MapTransform,
+};
+
+/// Successfully generated map
+pub struct GenerationResult {
+    pub map: Map,
+
+    /// seed of the successful attempt
+    pub seed: Seed,
+
+    /// all attempts, the last one is the successful one
+    pub attempts: Vec<Attempt>,
+
+    /// debug layers of the successful attempt, if enabled
+    pub debug_layers: Option<DebugLayers>,
+
+    /// route information of the generated map
+    pub report: GenerationReport,
+
+    /// durations of the walker, each post processing pass and export preprocessing of the
+    /// successful attempt
+    pub timings: StageTimings,
+}
+
+/// Collects all inputs of a map generation. Both the editor and the CLI create their generators
+/// using this builder, so the same inputs always result in the same map.
+#[derive(Clone)]
+pub struct GeneratorBuilder {
+    pub gen_config: GenerationConfig,
+    pub map_config: MapConfig,
+    pub thm_config: ThemeConfig,
+
+    /// if set, the difficulty is interpolated from gen_config towards this config
+    pub curve_end_config: Option<GenerationConfig>,
+
+    /// explicit difficulty curve, takes precedence over curve_end_config
+    pub difficulty_curve: Option<DifficultyCurve>,
+
+    /// if set, the map config is replaced with a layout generated from the seed
+    pub layout_config: Option<LayoutConfig>,
+
+    pub seed: Seed,
+
+    /// maximum amount of walker steps before the generation is aborted
+    pub max_steps: usize,
+
+    /// how failed generations are retried
+    pub retry_policy: RetryPolicy,
+
+    pub export_preprocess: bool,
+    pub capture_debug_layers: bool,
+
+    /// if disabled, panics are propagated to the caller instead of failing the attempt
+    pub catch_panics: bool,
+}
+
+impl Default for GeneratorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GeneratorBuilder {
+    pub fn new() -> GeneratorBuilder {
+        GeneratorBuilder {
+            gen_config: GenerationConfig::get_initial_config(),
+            map_config: MapConfig::get_initial_config(),
+            thm_config: ThemeConfig::default(),
+            curve_end_config: None,
+            difficulty_curve: None,
+            layout_config: None,
+            seed: Seed::random(),
+            max_steps: 200_000,
+            retry_policy: RetryPolicy::no_retry(),
+            export_preprocess: true,
+            capture_debug_layers: false,
+            catch_panics: true,
+        }
+    }
+
+    pub fn with_gen_config(mut self, gen_config: GenerationConfig) -> Self {
+        self.gen_config = gen_config;
+        self
+    }
+
+    /// use one of the bundled generation configs
+    pub fn with_gen_preset(self, name: &str) -> Result<Self, &'static str> {
+        let gen_config = find_gen_preset(name)?;
+        Ok(self.with_gen_config(gen_config))
+    }
+
+    pub fn with_map_config(mut self, map_config: MapConfig) -> Self {
+        self.map_config = map_config;
+        self
+    }
+
+    /// use one of the bundled map configs
+    pub fn with_map_preset(self, name: &str) -> Result<Self, &'static str> {
+        let map_config = MapConfig::get_all_configs()
+            .into_iter()
+            .find(|config| config.name == name)
+            .ok_or("map config not found")?;
+        Ok(self.with_map_config(map_config))
+    }
+
+    pub fn with_theme_config(mut self, thm_config: ThemeConfig) -> Self {
+        self.thm_config = thm_config;
+        self
+    }
+
+    pub fn with_curve_end_config(mut self, end_config: Option<GenerationConfig>) -> Self {
+        self.curve_end_config = end_config;
+        self
+    }
+
+    /// interpolate the difficulty towards one of the bundled generation configs
+    pub fn with_curve_end_preset(self, name: &str) -> Result<Self, &'static str> {
+        let end_config = find_gen_preset(name)?;
+        Ok(self.with_curve_end_config(Some(end_config)))
+    }
+
+    pub fn with_difficulty_curve(mut self, difficulty_curve: Option<DifficultyCurve>) -> Self {
+        self.difficulty_curve = difficulty_curve;
+        self
+    }
+
+    pub fn with_layout(mut self, layout_config: Option<LayoutConfig>) -> Self {
+        self.layout_config = layout_config;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: Seed) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_export_preprocess(mut self, export_preprocess: bool) -> Self {
+        self.export_preprocess = export_preprocess;
+        self
+    }
+
+    pub fn with_crop_margin(mut self, crop_margin: Option<usize>) -> Self {
+        self.thm_config.crop_margin = crop_margin;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: MapTransform) -> Self {
+        self.thm_config.transform = transform;
+        self
+    }
+
+    pub fn with_debug_layers(mut self, capture_debug_layers: bool) -> Self {
+        self.capture_debug_layers = capture_debug_layers;
+        self
+    }
+
+    pub fn with_catch_panics(mut self, catch_panics: bool) -> Self {
+        self.catch_panics = catch_panics;
+        self
+    }
+
+    /// map config that is used for the given seed, either the configured or a generated one
+    pub fn get_map_config(&self, seed: &Seed) -> Result<MapConfig, &'static str> {
+        match &self.layout_config {
+            Some(layout_config) => layout_config.generate(seed.clone(), &self.thm_config),
+            None => Ok(self.map_config.clone()),
+        }
+    }
+
+    /// map config that is used for the given seed after auto fitting, see get_map_config
+    pub fn get_fitted_map_config(&self, seed: &Seed) -> Result<MapConfig, &'static str> {
+        Ok(Generator::get_fitted_map_config(
+            &self.gen_config,
+            &self.get_map_config(seed)?,
+            &self.thm_config,
+            &self.get_difficulty_curve(),
+        ))
+    }
+
+    pub fn get_difficulty_curve(&self) -> Option<DifficultyCurve> {
+        self.difficulty_curve.clone().or_else(|| {
+            self.curve_end_config
+                .as_ref()
+                .map(|end_config| DifficultyCurve::from_start_end(&self.gen_config, end_config))
+        })
+    }
+
+    /// Validates all configs and creates an initial generator for the given seed, which can
+    /// then be stepped manually.
+    pub fn build(&self, seed: &Seed) -> Result<Generator, &'static str> {
+        self.build_with_map_config(seed, &self.get_map_config(seed)?)
+    }
+
+    fn build_with_map_config(
+        &self,
+        seed: &Seed,
+        map_config: &MapConfig,
+    ) -> Result<Generator, &'static str> {
+        let difficulty_curve = self.get_difficulty_curve();
+
+        self.gen_config.validate()?;
+        map_config.validate(&self.gen_config)?;
+        if let Some(difficulty_curve) = &difficulty_curve {
+            difficulty_curve.validate()?;
+        }
+
+        Generator::new_with_curve(
+            &self.gen_config,
+            map_config,
+            &self.thm_config,
+            seed.clone(),
+            difficulty_curve,
+        )
+    }
+
+    /// Generates an entire map. Failed attempts are retried according to the retry policy, panics
+    /// are caught and treated as failed attempts.
+    pub fn generate(&self) -> Result<GenerationResult, GenerationFailure> {
+        self.generate_observed(&mut |_| {}, &CancellationToken::new())
+    }
+
+    /// Same as generate, but reports the progress to an observer and aborts the generation
+    /// once the cancellation token is cancelled or its deadline is exceeded. The observer is
+    /// called every PROGRESS_STEP_INTERVAL walker steps, whenever a waypoint is reached and
+    /// before each post processing stage.
+    pub fn generate_observed(
+        &self,
+        observer: &mut dyn FnMut(&Progress),
+        cancel: &CancellationToken,
+    ) -> Result<GenerationResult, GenerationFailure> {
+        let start = Instant::now();
+        let mut attempts = Vec::new();
+        let mut index = 0;
+
+        loop {
+            let seed = RetryPolicy::get_attempt_seed(&self.seed, index);
+            let result = if self.catch_panics {
+                panic::catch_unwind(AssertUnwindSafe(|| {
+                    self.generate_attempt(&seed, index, start, &mut *observer, cancel)
+                }))
+                .unwrap_or(Err(AttemptFailure::new(
+                    FailureKind::Panic,
+                    "generation panicked",
+                )))
+            } else {
+                self.generate_attempt(&seed, index, start, &mut *observer, cancel)
+            };
+
+            match result {
+                Ok((map, debug_layers, report, timings)) => {
+                    attempts.push(Attempt {
+                        index,
+                        seed: seed.clone(),
+                        failure: None,
+                    });
+
+                    return Ok(GenerationResult {
+                        map,
+                        seed,
+                        attempts,
+                        debug_layers,
+                        report,
+                        timings,
+                    });
+                }
+                Err(failure) => {
+                    let retry = self.retry_policy.should_retry(&failure, index);
+                    attempts.push(Attempt {
+                        index,
+                        seed,
+                        failure: Some(failure),
+                    });
+
+                    if !retry {
+                        return Err(GenerationFailure { attempts });
+                    }
+                }
+            }
+
+            index += 1;
+        }
+    }
+
+    fn generate_attempt(
+        &self,
+        seed: &Seed,
+        attempt: usize,
+        start: Instant,
+        observer: &mut dyn FnMut(&Progress),
+        cancel: &CancellationToken,
+    ) -> Result<(Map, Option<DebugLayers>, GenerationReport, StageTimings), AttemptFailure> {
+        let config_failure = |err| AttemptFailure::new(FailureKind::Config, err);
+        let map_config = self.get_map_config(seed).map_err(config_failure)?;
+        let mut gen = self
+            .build_with_map_config(seed, &map_config)
+            .map_err(config_failure)?;
+        let mut debug_layers = self
+            .capture_debug_layers
+            .then(|| DebugLayers::new((gen.map.width, gen.map.height), 0.5, None));
+
+        let mut report = |gen: &Generator, stage: GenerationStage| {
+            observer(&Progress {
+                stage,
+                attempt,
+                steps: gen.walker.steps,
+                waypoint_index: gen.walker.goal_index,
+                waypoint_count: gen.walker.waypoints.len(),
+                elapsed: start.elapsed(),
+            })
+        };
+
+        let (report, timings) = self.run(
+            &mut gen,
+            &map_config,
+            &mut debug_layers,
+            &mut report,
+            cancel,
+        )?;
+
+        self.gen_config
+            .acceptance
+            .check_map(&gen.map, &report)
+            .map_err(|err| AttemptFailure::new(FailureKind::Rejected, err))?;
+
+        Ok((gen.map, debug_layers, report, timings))
+    }
+
+    /// Performs all walker steps, post processing and export preprocessing of one attempt and
+    /// measures the duration of each stage.
+    fn run(
+        &self,
+        gen: &mut Generator,
+        map_config: &MapConfig,
+        debug_layers: &mut Option<DebugLayers>,
+        report: &mut dyn FnMut(&Generator, GenerationStage),
+        cancel: &CancellationToken,
+    ) -> Result<(GenerationReport, StageTimings), AttemptFailure> {
+        let cancelled = |err| AttemptFailure::new(FailureKind::Cancelled, err);
+        let mut timings = StageTimings::new();
+
+        report(gen, GenerationStage::Walker);
+        let walker_start = Instant::now();
+        for step in 0..self.max_steps {
+            if gen.walker.finished {
+                break;
+            }
+            cancel.check().map_err(cancelled)?;
+
+            let goal_index = gen.walker.goal_index;
+            gen.step(&self.gen_config, false, debug_layers)
+                .map_err(|err| AttemptFailure::new(FailureKind::Walker, err))?;
+
+            if gen.walker.goal_index != goal_index || (step + 1) % PROGRESS_STEP_INTERVAL == 0 {
+                report(gen, GenerationStage::Walker);
+            }
+        }
+
+        timings.push((GenerationStage::Walker, walker_start.elapsed()));
+
+        let mut runner = PipelineRunner::default();
+        while let Some(stage) = runner.get_next_stage(&self.gen_config) {
+            cancel.check().map_err(cancelled)?;
+            report(gen, GenerationStage::PostProcessing(stage));
+            runner
+                .step(gen, &self.gen_config, &self.thm_config, debug_layers)
+                .map_err(|err| AttemptFailure::new(FailureKind::PostProcessing, err))?;
+        }
+
+        timings.extend(
+            runner
+                .timings
+                .iter()
+                .map(|(stage, duration)| (GenerationStage::PostProcessing(*stage), *duration)),
+        );
+
+        let mut gen_report = GenerationReport::new(gen, map_config, &runner.ctx);
+
+        if self.export_preprocess {
+            cancel.check().map_err(cancelled)?;
+            report(gen, GenerationStage::ExportPreprocess);
+            let export_start = Instant::now();
+            let export = gen.export_preprocess(&self.thm_config, debug_layers, false);
+            timings.push((GenerationStage::ExportPreprocess, export_start.elapsed()));
+            gen_report.apply_export(&export, &gen.map);
+        }
+
+        report(gen, GenerationStage::Finished);
+
+        Ok((gen_report, timings))
+    }
+}
+
+fn find_gen_preset(name: &str) -> Result<GenerationConfig, &'static str> {
+    GenerationConfig::get_all_configs()
+        .into_iter()
+        .find(|config| config.name == name)
+        .ok_or("gen config not found")
+}
@@ -1,15 +1,28 @@
+use crate::metrics::AcceptanceCriteria;
 use crate::noise::Noise;
+use crate::patterns::ForbiddenPattern;
+use crate::pipeline::PostPass;
 use crate::position::{Position, ShiftDirection};
 use crate::random::{Random, RandomDistConfig};
+use crate::transform::MapTransform;
 use log::warn;
 use rust_embed::RustEmbed;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
 use std::io::Write;
 
 pub const MAP_LENGTH_BASELINE: f32 = 650.0;
 
+fn lerp_f32(a: f32, b: f32, weight: f32) -> f32 {
+    a * (1.0 - weight) + b * weight
+}
+
+fn lerp_usize(a: usize, b: usize, weight: f32) -> usize {
+    lerp_f32(a as f32, b as f32, weight).round() as usize
+}
+
 pub fn get_config_points(gen_config: &GenerationConfig, map_config: &MapConfig) -> f32 {
     gen_config.difficulty * (map_config.get_map_length() / MAP_LENGTH_BASELINE)
 }
@@ -35,6 +48,16 @@ pub struct MapConfig {
 
     /// height of the map
     pub height: usize,
+
+    /// optional generation config overrides for individual waypoints (by waypoint index). These
+    /// are active while the walker travels towards the respective waypoint.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub waypoint_overrides: BTreeMap<usize, WaypointOverride>,
+
+    /// if enabled, width and height are ignored and the minimal map size that fits all
+    /// waypoints is used instead
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_fit: bool,
 }
 
 impl MapConfig {
@@ -81,6 +104,115 @@ impl MapConfig {
             .map(|w| w[0].distance(&w[1]))
             .sum()
     }
+
+    /// Shifts all waypoints and resizes the map to the minimal size that still has enough room
+    /// for the spawn and the lock padding at the map border, which depends on the lock size.
+    pub fn fit_to_waypoints(
+        &mut self,
+        gen_config: &GenerationConfig,
+        thm_config: &ThemeConfig,
+        lock_size: usize,
+    ) {
+        if self.waypoints.is_empty() {
+            return;
+        }
+
+        // lock padding at map border + room for largest kernel around (shifted sub-)waypoints
+        let border = 2 * lock_size + 2 + gen_config.subwaypoint_max_shift_dist.ceil() as usize;
+
+        // (left, right, top, bottom) room that is required around each waypoint
+        let (spawn_left, spawn_right, spawn_top, spawn_bottom) = thm_config.get_spawn_room();
+        let spawn_room = (
+            border.max(spawn_left),
+            border.max(spawn_right),
+            border.max(spawn_top),
+            border.max(spawn_bottom),
+        );
+        let waypoint_room = (border, border, border, border);
+
+        let mut min_x = isize::MAX;
+        let mut min_y = isize::MAX;
+        let mut max_x = isize::MIN;
+        let mut max_y = isize::MIN;
+        for (index, waypoint) in self.waypoints.iter().enumerate() {
+            let (left, right, top, bottom) = if index == 0 {
+                spawn_room
+            } else {
+                waypoint_room
+            };
+
+            min_x = min_x.min(waypoint.x as isize - left as isize);
+            min_y = min_y.min(waypoint.y as isize - top as isize);
+            max_x = max_x.max((waypoint.x + right) as isize);
+            max_y = max_y.max((waypoint.y + bottom) as isize);
+        }
+
+        for waypoint in self.waypoints.iter_mut() {
+            waypoint.x = (waypoint.x as isize - min_x) as usize;
+            waypoint.y = (waypoint.y as isize - min_y) as usize;
+        }
+
+        self.width = (max_x - min_x + 1) as usize;
+        self.height = (max_y - min_y + 1) as usize;
+    }
+
+    /// returns an error if the waypoint overrides would result in an invalid config
+    pub fn validate(&self, gen_config: &GenerationConfig) -> Result<(), &'static str> {
+        for (waypoint_index, waypoint_override) in self.waypoint_overrides.iter() {
+            if *waypoint_index >= self.waypoints.len() {
+                return Err("waypoint override for non existing waypoint");
+            }
+
+            waypoint_override.apply(gen_config).validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Overrides for a subset of the `GenerationConfig` which can be set for individual waypoints.
+/// Fields that are `None` fall back to the value of the base generation config.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct WaypointOverride {
+    pub inner_size_probs: Option<RandomDistConfig<usize>>,
+    pub outer_margin_probs: Option<RandomDistConfig<usize>>,
+    pub circ_probs: Option<RandomDistConfig<f32>>,
+    pub momentum_prob: Option<f32>,
+    pub shift_weights: Option<RandomDistConfig<ShiftDirection>>,
+    pub enable_pulse: Option<bool>,
+    pub waypoint_reached_dist: Option<usize>,
+}
+
+impl WaypointOverride {
+    /// returns a copy of the given generation config with all overrides applied
+    pub fn apply(&self, gen_config: &GenerationConfig) -> GenerationConfig {
+        let mut config = gen_config.clone();
+
+        if let Some(inner_size_probs) = &self.inner_size_probs {
+            config.inner_size_probs = inner_size_probs.clone();
+        }
+        if let Some(outer_margin_probs) = &self.outer_margin_probs {
+            config.outer_margin_probs = outer_margin_probs.clone();
+        }
+        if let Some(circ_probs) = &self.circ_probs {
+            config.circ_probs = circ_probs.clone();
+        }
+        if let Some(momentum_prob) = self.momentum_prob {
+            config.momentum_prob = momentum_prob;
+        }
+        if let Some(shift_weights) = &self.shift_weights {
+            config.shift_weights = shift_weights.clone();
+        }
+        if let Some(enable_pulse) = self.enable_pulse {
+            config.enable_pulse = enable_pulse;
+        }
+        if let Some(waypoint_reached_dist) = self.waypoint_reached_dist {
+            config.waypoint_reached_dist = waypoint_reached_dist;
+        }
+
+        config
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -128,6 +260,12 @@ pub struct GenerationConfig {
     pub plat_max_width: usize,
     pub plat_part_width: usize,
 
+    /// maps with a platform gap larger than plat_target_distance times this factor are rejected
+    pub plat_max_gap_factor: f32,
+
+    /// maps with a platform gap smaller than plat_target_distance times this factor are rejected
+    pub plat_min_gap_factor: f32,
+
     // ===================================[ ]==========================================
     /// probability for doing the last shift direction again
     pub momentum_prob: f32,
@@ -212,6 +350,24 @@ pub struct GenerationConfig {
 
     /// how far empty blocks need be be apart from "main path" for them to count as a dead end
     pub dead_end_threshold: usize,
+
+    // ===================================[ Guidance ]=====================================
+    /// if enabled, shifts are rated towards a coarse path through unlocked space instead of
+    /// straight towards the next waypoint
+    pub use_guidance: bool,
+
+    /// cell size of the downsampled lock grid that is used for guidance path finding
+    pub guidance_cell_size: usize,
+
+    // ===================================[ Post Processing ]==============================
+    /// post processing stages in order of execution
+    pub post_processing: Vec<PostPass>,
+
+    /// patterns that are detected and replaced by the forbidden patterns stage
+    pub forbidden_patterns: Vec<ForbiddenPattern>,
+
+    /// thresholds on the map metrics, successful maps that violate them are rejected
+    pub acceptance: AcceptanceCriteria,
 }
 
 impl GenerationConfig {
@@ -260,9 +416,170 @@ impl GenerationConfig {
             return Err("max subwaypoint distance must be >0");
         }
 
+        // check platform gap config
+        if self.plat_min_gap_factor < 0.0 || self.plat_min_gap_factor > self.plat_max_gap_factor {
+            return Err("platform gap factors must fulfill 0 <= min <= max");
+        }
+
+        // check guidance config
+        if self.use_guidance && self.guidance_cell_size == 0 {
+            return Err("guidance cell size must be >0");
+        }
+
+        PostPass::validate_pipeline(&self.post_processing)?;
+
+        for forbidden in self.forbidden_patterns.iter() {
+            forbidden.validate()?;
+        }
+
+        self.acceptance.validate()?;
+
         Ok(())
     }
 
+    /// size of the area that is locked around previous walker positions. This is the maximum
+    /// possible kernel size, which is max inner + max margin + 2
+    pub fn get_lock_size(&self) -> usize {
+        self.inner_size_probs.max_value().unwrap()
+            + self.outer_margin_probs.max_value().unwrap()
+            + 2
+    }
+
+    /// Linear interpolation between two generation configs. Numeric fields and probability
+    /// distributions are blended, all other fields are taken from the closer config. New fields
+    /// have to be handled here explicitly, see tests/config.rs.
+    pub fn lerp(&self, other: &GenerationConfig, weight: f32) -> GenerationConfig {
+        let closer = if weight < 0.5 { self } else { other };
+
+        GenerationConfig {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            version: self.version.clone(),
+            difficulty: lerp_f32(self.difficulty, other.difficulty, weight),
+            inner_rad_mut_prob: lerp_f32(self.inner_rad_mut_prob, other.inner_rad_mut_prob, weight),
+            inner_size_mut_prob: lerp_f32(
+                self.inner_size_mut_prob,
+                other.inner_size_mut_prob,
+                weight,
+            ),
+            outer_rad_mut_prob: lerp_f32(self.outer_rad_mut_prob, other.outer_rad_mut_prob, weight),
+            outer_size_mut_prob: lerp_f32(
+                self.outer_size_mut_prob,
+                other.outer_size_mut_prob,
+                weight,
+            ),
+            shift_weights: self.shift_weights.lerp(&other.shift_weights, weight),
+            plat_target_distance: lerp_usize(
+                self.plat_target_distance,
+                other.plat_target_distance,
+                weight,
+            ),
+            plat_max_freeze: lerp_usize(self.plat_max_freeze, other.plat_max_freeze, weight),
+            plat_height: lerp_usize(self.plat_height, other.plat_height, weight),
+            plat_min_width: lerp_usize(self.plat_min_width, other.plat_min_width, weight),
+            plat_max_width: lerp_usize(self.plat_max_width, other.plat_max_width, weight),
+            plat_part_width: lerp_usize(self.plat_part_width, other.plat_part_width, weight),
+            plat_max_gap_factor: lerp_f32(
+                self.plat_max_gap_factor,
+                other.plat_max_gap_factor,
+                weight,
+            ),
+            plat_min_gap_factor: lerp_f32(
+                self.plat_min_gap_factor,
+                other.plat_min_gap_factor,
+                weight,
+            ),
+            momentum_prob: lerp_f32(self.momentum_prob, other.momentum_prob, weight),
+            max_distance: lerp_f32(self.max_distance, other.max_distance, weight),
+            waypoint_reached_dist: lerp_usize(
+                self.waypoint_reached_dist,
+                other.waypoint_reached_dist,
+                weight,
+            ),
+            inner_size_probs: self.inner_size_probs.lerp(&other.inner_size_probs, weight),
+            outer_margin_probs: self
+                .outer_margin_probs
+                .lerp(&other.outer_margin_probs, weight),
+            circ_probs: self.circ_probs.lerp(&other.circ_probs, weight),
+            skip_length_bounds: (
+                lerp_usize(
+                    self.skip_length_bounds.0,
+                    other.skip_length_bounds.0,
+                    weight,
+                ),
+                lerp_usize(
+                    self.skip_length_bounds.1,
+                    other.skip_length_bounds.1,
+                    weight,
+                ),
+            ),
+            skip_min_spacing_sqr: lerp_usize(
+                self.skip_min_spacing_sqr,
+                other.skip_min_spacing_sqr,
+                weight,
+            ),
+            max_level_skip: lerp_usize(self.max_level_skip, other.max_level_skip, weight),
+            min_freeze_size: lerp_usize(self.min_freeze_size, other.min_freeze_size, weight),
+            enable_pulse: closer.enable_pulse,
+            pulse_straight_delay: lerp_usize(
+                self.pulse_straight_delay,
+                other.pulse_straight_delay,
+                weight,
+            ),
+            pulse_corner_delay: lerp_usize(
+                self.pulse_corner_delay,
+                other.pulse_corner_delay,
+                weight,
+            ),
+            pulse_max_kernel_size: lerp_usize(
+                self.pulse_max_kernel_size,
+                other.pulse_max_kernel_size,
+                weight,
+            ),
+            fade_steps: lerp_usize(self.fade_steps, other.fade_steps, weight),
+            fade_max_size: lerp_usize(self.fade_max_size, other.fade_max_size, weight),
+            fade_min_size: lerp_usize(self.fade_min_size, other.fade_min_size, weight),
+            max_subwaypoint_dist: lerp_f32(
+                self.max_subwaypoint_dist,
+                other.max_subwaypoint_dist,
+                weight,
+            ),
+            subwaypoint_max_shift_dist: lerp_f32(
+                self.subwaypoint_max_shift_dist,
+                other.subwaypoint_max_shift_dist,
+                weight,
+            ),
+            skip_invalid_waypoints: closer.skip_invalid_waypoints,
+            pos_lock_max_dist: lerp_f32(self.pos_lock_max_dist, other.pos_lock_max_dist, weight),
+            pos_lock_max_delay: lerp_usize(
+                self.pos_lock_max_delay,
+                other.pos_lock_max_delay,
+                weight,
+            ),
+            enable_kernel_lock: closer.enable_kernel_lock,
+            waypoint_lock_distance: lerp_usize(
+                self.waypoint_lock_distance,
+                other.waypoint_lock_distance,
+                weight,
+            ),
+            use_dead_end_removal: closer.use_dead_end_removal,
+            dead_end_threshold: lerp_usize(
+                self.dead_end_threshold,
+                other.dead_end_threshold,
+                weight,
+            ),
+            use_guidance: closer.use_guidance,
+            guidance_cell_size: lerp_usize(
+                self.guidance_cell_size,
+                other.guidance_cell_size,
+                weight,
+            ),
+            post_processing: closer.post_processing.clone(),
+            forbidden_patterns: closer.forbidden_patterns.clone(),
+            acceptance: closer.acceptance.clone(),
+        }
+    }
+
     pub fn random(rnd: &mut Random) -> GenerationConfig {
         let use_locking = rnd.get_bool_with_prob(0.5);
 
@@ -402,6 +719,98 @@ impl GenerationConfig {
     }
 }
 
+/// Generation config that is reached at a certain progress of the walker, in range [0, 1]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct CurveKeyframe {
+    pub progress: f32,
+    pub config: GenerationConfig,
+}
+
+/// Difficulty curve that interpolates between generation configs along the map. The progress of
+/// the walker is determined by the index of its current waypoint.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct DifficultyCurve {
+    /// keyframes, sorted by progress
+    pub keyframes: Vec<CurveKeyframe>,
+}
+
+impl DifficultyCurve {
+    /// curve that linearly interpolates from start to end config along the entire map
+    pub fn from_start_end(start: &GenerationConfig, end: &GenerationConfig) -> DifficultyCurve {
+        DifficultyCurve {
+            keyframes: vec![
+                CurveKeyframe {
+                    progress: 0.0,
+                    config: start.clone(),
+                },
+                CurveKeyframe {
+                    progress: 1.0,
+                    config: end.clone(),
+                },
+            ],
+        }
+    }
+
+    /// returns an error if the curve or any of the keyframe configs is invalid
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.keyframes.is_empty() {
+            return Err("difficulty curve has no keyframes");
+        }
+
+        for keyframe in self.keyframes.iter() {
+            if !(0.0..=1.0).contains(&keyframe.progress) {
+                return Err("keyframe progress must be in [0, 1]");
+            }
+            keyframe.config.validate()?;
+        }
+
+        if self
+            .keyframes
+            .windows(2)
+            .any(|w| w[0].progress > w[1].progress)
+        {
+            return Err("keyframes must be sorted by progress");
+        }
+
+        Ok(())
+    }
+
+    /// interpolated generation config for the given progress
+    pub fn get_config(&self, progress: f32) -> Result<GenerationConfig, &'static str> {
+        let (Some(first), Some(last)) = (self.keyframes.first(), self.keyframes.last()) else {
+            return Err("difficulty curve has no keyframes");
+        };
+
+        if progress <= first.progress {
+            return Ok(first.config.clone());
+        }
+
+        for pair in self.keyframes.windows(2) {
+            let (start, end) = (&pair[0], &pair[1]);
+            if progress <= end.progress {
+                let range = end.progress - start.progress;
+                let weight = if range > 0.0 {
+                    (progress - start.progress) / range
+                } else {
+                    1.0
+                };
+                return Ok(start.config.lerp(&end.config, weight));
+            }
+        }
+
+        Ok(last.config.clone())
+    }
+
+    /// maximum lock size of all keyframes, as interpolated kernels never exceed these
+    pub fn get_lock_size(&self) -> usize {
+        self.keyframes
+            .iter()
+            .map(|keyframe| keyframe.config.get_lock_size())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
 impl Default for GenerationConfig {
     /// Default trait should mainly be used to get default values for individual arguments
     /// instead of being used as an actual generation config. (use get_initial_config())
@@ -423,6 +832,8 @@ impl Default for GenerationConfig {
             plat_min_width: 3,
             plat_max_width: 10,
             plat_part_width: 1,
+            plat_max_gap_factor: 1.5,
+            plat_min_gap_factor: 0.5,
             momentum_prob: 0.01,
             max_distance: 3.0,
             waypoint_reached_dist: 250,
@@ -449,6 +860,11 @@ impl Default for GenerationConfig {
             waypoint_lock_distance: 10,
             use_dead_end_removal: true,
             dead_end_threshold: 10,
+            use_guidance: false,
+            guidance_cell_size: 6,
+            post_processing: PostPass::default_pipeline(),
+            forbidden_patterns: Vec::new(),
+            acceptance: AcceptanceCriteria::default(),
         }
     }
 }
@@ -467,11 +883,15 @@ impl Default for MapConfig {
             ],
             width: 300,
             height: 300,
+            waypoint_overrides: BTreeMap::new(),
+            auto_fit: false,
         }
     }
 }
 
 /// Configuration for map visuals and appearance, isolated from map and generation config.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ThemeConfig {
     /// spawn width
     pub spawn_width: usize,
@@ -503,6 +923,13 @@ pub struct ThemeConfig {
     pub background_noise_invert: bool,
     pub background_noise_threshold: f32,
     pub background_noise_type: Noise,
+
+    /// if set, unused solid border is cropped before export, so that only this many blocks
+    /// remain around the used area of the map
+    pub crop_margin: Option<usize>,
+
+    /// transform that is applied before export
+    pub transform: MapTransform,
 }
 
 impl Default for ThemeConfig {
@@ -523,10 +950,24 @@ impl Default for ThemeConfig {
             background_noise_invert: false,
             background_noise_threshold: 0.00,
             background_noise_type: Noise::Perlin,
+            crop_margin: None,
+            transform: MapTransform::Random,
         }
     }
 }
 
+impl ThemeConfig {
+    /// (left, right, top, bottom) room that the spawn requires around the first waypoint
+    pub fn get_spawn_room(&self) -> (usize, usize, usize, usize) {
+        (
+            self.spawn_width,
+            self.spawn_margin + 1,
+            self.spawn_height / 2 + 1,
+            self.spawn_height / 2 + 1,
+        )
+    }
+}
+
 pub trait Config {
     fn get_name(&self) -> &String;
 }
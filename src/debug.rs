@@ -1,8 +1,10 @@
 use macroquad::color::Color;
-use ndarray::Array2;
+use ndarray::{s, Array2};
 
 use std::collections::BTreeMap;
 
+use crate::{position::Position, transform::MapTransform};
+
 #[derive(Debug)]
 pub struct FloatLayer {
     pub grid: Array2<Option<f32>>,
@@ -59,6 +61,30 @@ impl DebugLayers {
             .grid
     }
 
+    /// transforms all layers the same way as the map
+    pub fn transform(&mut self, transform: &MapTransform) {
+        for layer in self.bool_layers.values_mut() {
+            transform.apply_array(&mut layer.grid);
+        }
+        for layer in self.float_layers.values_mut() {
+            transform.apply_array(&mut layer.grid);
+        }
+    }
+
+    /// crops all layers to the area with the given top left corner and size, like the map
+    pub fn crop(&mut self, top_left: &Position, width: usize, height: usize) {
+        let area = s![
+            top_left.x..top_left.x + width,
+            top_left.y..top_left.y + height
+        ];
+        for layer in self.bool_layers.values_mut() {
+            layer.grid = layer.grid.slice(area).to_owned();
+        }
+        for layer in self.float_layers.values_mut() {
+            layer.grid = layer.grid.slice(area).to_owned();
+        }
+    }
+
     pub fn new(
         shape: (usize, usize),
         default_alpha: f32,
@@ -129,6 +155,30 @@ impl DebugLayers {
                 "plat_part",
                 BoolLayer::new(shape, Color::new(0.76, 0.16, 0.88, default_alpha), false),
             ),
+            (
+                "reachable",
+                BoolLayer::new(shape, Color::new(0.2, 0.6, 0.86, default_alpha), false),
+            ),
+            (
+                "unreachable",
+                BoolLayer::new(shape, Color::new(0.91, 0.3, 0.24, default_alpha), true),
+            ),
+            (
+                "patterns",
+                BoolLayer::new(shape, Color::new(0.95, 0.5, 0.75, default_alpha), true),
+            ),
+            (
+                "stage_added",
+                BoolLayer::new(shape, Color::new(0.18, 0.8, 0.44, default_alpha), false),
+            ),
+            (
+                "stage_removed",
+                BoolLayer::new(shape, Color::new(0.91, 0.3, 0.24, default_alpha), false),
+            ),
+            (
+                "stage_changed",
+                BoolLayer::new(shape, Color::new(0.95, 0.77, 0.06, default_alpha), false),
+            ),
         ]);
 
         let float_layers: BTreeMap<&'static str, FloatLayer> = BTreeMap::from([
@@ -172,6 +222,14 @@ impl DebugLayers {
                     Color::new(0.0, 0.0, 1.0, default_alpha),
                 ),
             ),
+            (
+                "plat_gap",
+                FloatLayer::new(
+                    shape,
+                    Color::new(0.0, 1.0, 0.0, default_alpha),
+                    Color::new(1.0, 0.0, 0.0, default_alpha),
+                ),
+            ),
         ]);
 
         // initialize using keys from all debug layers, or re-use if possible
@@ -0,0 +1,328 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::MAP_LENGTH_BASELINE, metrics::MapMetrics};
+
+/// corridors up to this width (including freeze) are considered narrow passages
+const NARROW_CORRIDOR_WIDTH: usize = 3;
+
+/// platform gaps are given relative to this flood fill distance
+const PLATFORM_GAP_BASELINE: f32 = 100.0;
+
+/// direction changes are counted per this many main path blocks
+const TURN_DENSITY_BASELINE: f32 = 100.0;
+
+/// small regularization when fitting weights, keeps them stable for correlated features
+const FIT_REGULARIZATION: f32 = 1e-3;
+
+/// weights and gradients below this are considered zero when fitting weights
+const FIT_TOLERANCE: f64 = 1e-9;
+
+const FEATURE_COUNT: usize = 5;
+
+/// each feature is usually freed at most a few times when fitting weights
+const MAX_FIT_ITERATIONS: usize = 3 * FEATURE_COUNT;
+
+/// Features of a generated map that contribute to its difficulty, mostly in the range [0, 1]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DifficultyFeatures {
+    /// share of the main path that is a narrow passage
+    pub narrow_ratio: f32,
+
+    /// freeze exposure along the main path
+    pub freeze_ratio: f32,
+
+    /// largest gap between two platforms, the entire main path if there are no platforms
+    pub platform_gap: f32,
+
+    /// share of the main path that goes upwards
+    pub climb_ratio: f32,
+
+    /// direction changes per TURN_DENSITY_BASELINE main path blocks
+    pub turn_density: f32,
+}
+
+impl DifficultyFeatures {
+    pub fn new(metrics: &MapMetrics) -> DifficultyFeatures {
+        let path_blocks = (metrics.main_path_length + 1) as f32;
+        let narrow_blocks: usize = metrics
+            .corridor_width_histogram
+            .iter()
+            .take(NARROW_CORRIDOR_WIDTH + 1)
+            .sum();
+        let max_platform_gap = metrics
+            .platform_gap_bounds
+            .map_or(metrics.main_path_length, |(_, max)| max);
+
+        DifficultyFeatures {
+            narrow_ratio: narrow_blocks as f32 / path_blocks,
+            freeze_ratio: metrics.freeze_ratio,
+            platform_gap: max_platform_gap as f32 / PLATFORM_GAP_BASELINE,
+            climb_ratio: metrics.climb_height as f32 / path_blocks,
+            turn_density: metrics.direction_changes as f32 * TURN_DENSITY_BASELINE / path_blocks,
+        }
+    }
+
+    fn as_array(&self) -> [f32; FEATURE_COUNT] {
+        [
+            self.narrow_ratio,
+            self.freeze_ratio,
+            self.platform_gap,
+            self.climb_ratio,
+            self.turn_density,
+        ]
+    }
+}
+
+/// Linear weights of the difficulty features, see difficulty_calibration for fitting them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DifficultyWeights {
+    pub bias: f32,
+    pub narrow_ratio: f32,
+    pub freeze_ratio: f32,
+    pub platform_gap: f32,
+    pub climb_ratio: f32,
+    pub turn_density: f32,
+}
+
+impl Default for DifficultyWeights {
+    /// neutral weights that are not calibrated, every feature counts the same. Calibrated weights
+    /// can be fitted against labeled maps with
+    /// `cargo run --bin difficulty_calibration -- --labels <labels.json> --output <weights.json>`
+    /// and passed to the cli with `--difficulty-weights <weights.json>`.
+    fn default() -> DifficultyWeights {
+        DifficultyWeights {
+            bias: 0.0,
+            narrow_ratio: 1.0,
+            freeze_ratio: 1.0,
+            platform_gap: 1.0,
+            climb_ratio: 1.0,
+            turn_density: 1.0,
+        }
+    }
+}
+
+impl DifficultyWeights {
+    fn from_array(bias: f32, weights: [f32; FEATURE_COUNT]) -> DifficultyWeights {
+        DifficultyWeights {
+            bias,
+            narrow_ratio: weights[0],
+            freeze_ratio: weights[1],
+            platform_gap: weights[2],
+            climb_ratio: weights[3],
+            turn_density: weights[4],
+        }
+    }
+
+    fn as_array(&self) -> [f32; FEATURE_COUNT] {
+        [
+            self.narrow_ratio,
+            self.freeze_ratio,
+            self.platform_gap,
+            self.climb_ratio,
+            self.turn_density,
+        ]
+    }
+
+    /// estimated difficulty on the same scale as GenerationConfig::difficulty
+    pub fn estimate(&self, features: &DifficultyFeatures) -> f32 {
+        let difficulty: f32 = self
+            .as_array()
+            .iter()
+            .zip(features.as_array())
+            .map(|(weight, feature)| weight * feature)
+            .sum();
+
+        (self.bias + difficulty).max(0.0)
+    }
+
+    /// Least squares fit of the weights, so that the estimated difficulties match the labeled
+    /// difficulties as close as possible. Every feature makes a map harder, so feature weights
+    /// are constrained to be non-negative using the Lawson-Hanson active set method, the bias is
+    /// not constrained.
+    pub fn fit(samples: &[(DifficultyFeatures, f32)]) -> Result<DifficultyWeights, &'static str> {
+        if samples.len() <= FEATURE_COUNT {
+            return Err("not enough samples to fit difficulty weights");
+        }
+
+        // normal equations (X^T X + r I) w = X^T y, the last entry of w is the bias
+        const N: usize = FEATURE_COUNT + 1;
+        let mut lhs = [[0.0f64; N]; N];
+        let mut rhs = [0.0f64; N];
+        for (features, difficulty) in samples.iter() {
+            let mut row = [1.0f64; N];
+            for (value, feature) in row.iter_mut().zip(features.as_array()) {
+                *value = feature as f64;
+            }
+
+            for i in 0..N {
+                for j in 0..N {
+                    lhs[i][j] += row[i] * row[j];
+                }
+                rhs[i] += row[i] * *difficulty as f64;
+            }
+        }
+        for (i, lhs_row) in lhs.iter_mut().enumerate().take(FEATURE_COUNT) {
+            lhs_row[i] += FIT_REGULARIZATION as f64 * samples.len() as f64;
+        }
+
+        let solve = |passive: &[bool; FEATURE_COUNT]| {
+            let (lhs, rhs) = get_active_system(lhs, rhs, passive);
+            solve_linear_system(lhs, rhs)
+        };
+
+        // start with all feature weights fixed to zero, then free the feature that reduces the
+        // error the most until no feature can reduce it any further
+        let mut passive = [false; FEATURE_COUNT];
+        let mut solution = solve(&passive)?;
+        for _ in 0..MAX_FIT_ITERATIONS {
+            let gradient: Vec<f64> = (0..FEATURE_COUNT)
+                .map(|i| rhs[i] - (0..N).map(|j| lhs[i][j] * solution[j]).sum::<f64>())
+                .collect();
+            let Some(feature) = (0..FEATURE_COUNT)
+                .filter(|i| !passive[*i] && gradient[*i] > FIT_TOLERANCE)
+                .max_by(|a, b| gradient[*a].total_cmp(&gradient[*b]))
+            else {
+                break;
+            };
+            passive[feature] = true;
+
+            // if the unconstrained solution has negative weights, move towards it as far as
+            // possible and fix the weights that reached zero
+            loop {
+                let candidate = solve(&passive)?;
+                let step = (0..FEATURE_COUNT)
+                    .filter(|i| passive[*i] && candidate[*i] < 0.0)
+                    .map(|i| solution[i] / (solution[i] - candidate[i]))
+                    .min_by(f64::total_cmp);
+
+                let Some(step) = step else {
+                    solution = candidate;
+                    break;
+                };
+                for (value, candidate) in solution.iter_mut().zip(candidate) {
+                    *value += step * (candidate - *value);
+                }
+                for (i, is_passive) in passive.iter_mut().enumerate() {
+                    if *is_passive && solution[i] <= FIT_TOLERANCE {
+                        *is_passive = false;
+                        solution[i] = 0.0;
+                    }
+                }
+            }
+        }
+
+        let mut weights = [0.0; FEATURE_COUNT];
+        for (weight, value) in weights.iter_mut().zip(solution) {
+            *weight = value as f32;
+        }
+
+        Ok(DifficultyWeights::from_array(
+            solution[FEATURE_COUNT] as f32,
+            weights,
+        ))
+    }
+
+    pub fn load(path: &str) -> Result<DifficultyWeights, &'static str> {
+        let serialized =
+            fs::read_to_string(path).map_err(|_| "failed to read difficulty weights")?;
+        serde_json::from_str(&serialized).map_err(|_| "failed to deserialize difficulty weights")
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), &'static str> {
+        let serialized = serde_json::to_string_pretty(self)
+            .map_err(|_| "failed to serialize difficulty weights")?;
+        fs::write(path, serialized).map_err(|_| "failed to write difficulty weights")
+    }
+}
+
+/// difficulty of a generated map and the resulting points
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DifficultyEstimate {
+    pub features: DifficultyFeatures,
+    pub difficulty: f32,
+
+    /// points comparable to get_config_points, using the estimated difficulty
+    pub points: f32,
+}
+
+impl DifficultyEstimate {
+    pub fn new(metrics: &MapMetrics, weights: &DifficultyWeights) -> DifficultyEstimate {
+        let features = DifficultyFeatures::new(metrics);
+        let difficulty = weights.estimate(&features);
+
+        DifficultyEstimate {
+            features,
+            difficulty,
+            points: difficulty * (metrics.map_length / MAP_LENGTH_BASELINE),
+        }
+    }
+}
+
+/// labeled map for calibrating the difficulty weights, the map is generated from its inputs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DifficultyLabel {
+    pub gen_config: String,
+    pub map_config: String,
+    pub seed: u64,
+    pub difficulty: f32,
+}
+
+pub fn load_difficulty_labels(path: &str) -> Result<Vec<DifficultyLabel>, &'static str> {
+    let serialized = fs::read_to_string(path).map_err(|_| "failed to read difficulty labels")?;
+    serde_json::from_str(&serialized).map_err(|_| "failed to deserialize difficulty labels")
+}
+
+/// Normal equations where the weights of all non-passive features are fixed to zero, by
+/// replacing their rows and columns with the identity.
+fn get_active_system<const N: usize>(
+    mut lhs: [[f64; N]; N],
+    mut rhs: [f64; N],
+    passive: &[bool; FEATURE_COUNT],
+) -> ([[f64; N]; N], [f64; N]) {
+    for (feature, _) in passive.iter().enumerate().filter(|(_, passive)| !**passive) {
+        lhs[feature] = [0.0; N];
+        for row in lhs.iter_mut() {
+            row[feature] = 0.0;
+        }
+        lhs[feature][feature] = 1.0;
+        rhs[feature] = 0.0;
+    }
+
+    (lhs, rhs)
+}
+
+/// gaussian elimination with partial pivoting
+fn solve_linear_system<const N: usize>(
+    mut lhs: [[f64; N]; N],
+    mut rhs: [f64; N],
+) -> Result<[f64; N], &'static str> {
+    for col in 0..N {
+        let pivot = (col..N)
+            .max_by(|a, b| lhs[*a][col].abs().total_cmp(&lhs[*b][col].abs()))
+            .unwrap();
+        if lhs[pivot][col].abs() < 1e-12 {
+            return Err("difficulty features are linearly dependent");
+        }
+        lhs.swap(col, pivot);
+        rhs.swap(col, pivot);
+
+        let pivot_row = lhs[col];
+        for row in (col + 1)..N {
+            let factor = lhs[row][col] / pivot_row[col];
+            for (value, pivot) in lhs[row].iter_mut().zip(pivot_row).skip(col) {
+                *value -= factor * pivot;
+            }
+            rhs[row] -= factor * rhs[col];
+        }
+    }
+
+    let mut solution = [0.0; N];
+    for row in (0..N).rev() {
+        let sum: f64 = ((row + 1)..N).map(|k| lhs[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - sum) / lhs[row][row];
+    }
+
+    Ok(solution)
+}
@@ -1,16 +1,30 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    str::FromStr,
+};
 
 const STEPS_PER_FRAME: usize = 50;
+const SNAPSHOT_STEP_INTERVAL: usize = 100;
+const MAX_SNAPSHOTS: usize = 32;
 
 use crate::{
     args::EditorArgs,
+    builder::GeneratorBuilder,
     config::{GenerationConfig, MapConfig, ThemeConfig},
     debug::DebugLayers,
     generator::Generator,
     gui,
+    layout::LayoutConfig,
     map_camera::MapCamera,
+    pipeline::PipelineRunner,
     random::Seed,
+    report::GenerationReport,
+    retry::{AttemptFailure, FailureKind, RetryPolicy},
+    snapshot::{GenerationState, Timeline},
+    transform::ExportTransform,
 };
+use clap::crate_version;
 use egui::{epaint::Shadow, Color32, Frame, Margin};
 use log::warn;
 use std::env;
@@ -75,11 +89,28 @@ pub struct Editor {
     pub thm_config: ThemeConfig,
 
     pub init_gen_configs: Vec<GenerationConfig>,
+
+    /// if set, the difficulty is interpolated from gen_config to this config along the map
+    pub curve_end_config: Option<GenerationConfig>,
+
     pub init_map_configs: Vec<MapConfig>,
+
+    /// whether to generate a new map config from the seed for every generation
+    pub random_layout: bool,
+    pub layout_config: LayoutConfig,
     pub debug_layers: Option<DebugLayers>,
     pub average_fps: f32,
     pub gen: Generator,
 
+    /// state of the current post processing, None until the walker is finished
+    pub post_runner: Option<PipelineRunner>,
+
+    /// snapshots of the current generation
+    pub timeline: Timeline,
+
+    /// whether to record snapshots during generation
+    pub use_snapshots: bool,
+
     pub user_seed: Seed,
     pub user_seed_str: String,
     pub seed_input_type: SeedType,
@@ -114,9 +145,21 @@ pub struct Editor {
     /// whether to keep using the same seed for next generations
     pub fixed_seed: bool,
 
-    /// whether to keep using the same seed for next generations
+    /// whether to retry failed generations with a seed derived from the original seed
     pub retry_on_failure: bool,
 
+    /// defines which failures are retried
+    pub retry_policy: RetryPolicy,
+
+    /// index of the current attempt, 0 unless a failed generation is retried
+    pub retry_attempt: usize,
+
+    /// seed of the first attempt, retry seeds are derived from it
+    pub original_seed: Seed,
+
+    /// seed for the next generation, if a failed generation is retried
+    retry_seed: Option<Seed>,
+
     /// Whether to perform map export preprocessing such as generation of noise layers.
     /// This is computational expensive and should only be done for debugging purposes,
     /// or if the map is intended to be exported.
@@ -153,7 +196,10 @@ impl Editor {
             debug_layers: None,
             disable_debug_layers: args.disable_debug,
             init_gen_configs,
+            curve_end_config: None,
             init_map_configs,
+            random_layout: false,
+            layout_config: LayoutConfig::default(),
             canvas: None,
             egui_wants_mouse: None,
             average_fps: 0.0,
@@ -163,6 +209,10 @@ impl Editor {
             thm_config: ThemeConfig::default(),
             steps_per_frame: STEPS_PER_FRAME,
             gen,
+            post_runner: None,
+            timeline: Timeline::new(SNAPSHOT_STEP_INTERVAL, MAX_SNAPSHOTS),
+            use_snapshots: true,
+            original_seed: user_seed.clone(),
             user_seed,
             user_seed_str: String::new(),
             seed_input_type: SeedType::BASE64,
@@ -172,6 +222,9 @@ impl Editor {
             edit_gen_config: false,
             edit_map_config: false,
             retry_on_failure: false,
+            retry_policy: RetryPolicy::default().with_max_attempts(usize::MAX),
+            retry_attempt: 0,
+            retry_seed: None,
             show_theme_widget: false,
             show_debug_widget: false,
             show_debug_layers: false,
@@ -292,14 +345,20 @@ impl Editor {
 
     pub fn set_playing(&mut self) {
         if self.is_setup() {
-            self.initialize_generator();
+            if let Err(err) = self.initialize_generator() {
+                warn!("couldn't initialize generator: {}", err);
+                return;
+            }
         }
         self.state = EditorState::Playing(PlayingState::Continuous);
     }
 
     pub fn set_single_step(&mut self) {
         if self.is_setup() {
-            self.initialize_generator();
+            if let Err(err) = self.initialize_generator() {
+                warn!("couldn't initialize generator: {}", err);
+                return;
+            }
         }
         self.state = EditorState::Playing(PlayingState::SingleStep);
     }
@@ -312,22 +371,274 @@ impl Editor {
         self.state = EditorState::Paused(PausedState::Stopped);
     }
 
-    fn initialize_generator(&mut self) {
-        if !self.fixed_seed {
-            self.user_seed = Seed::from_random(&mut self.gen.rnd);
+    /// builder with the current editor settings, generates the same map as the editor
+    pub fn get_builder(&self) -> GeneratorBuilder {
+        GeneratorBuilder::new()
+            .with_gen_config(self.gen_config.clone())
+            .with_map_config(self.map_config.clone())
+            .with_theme_config(self.thm_config.clone())
+            .with_curve_end_config(self.curve_end_config.clone())
+            .with_layout(self.random_layout.then(|| self.layout_config.clone()))
+            .with_seed(self.user_seed.clone())
+            .with_export_preprocess(self.export_preprocess)
+    }
+
+    fn initialize_generator(&mut self) -> Result<(), &'static str> {
+        if let Some(retry_seed) = self.retry_seed.take() {
+            self.user_seed = retry_seed;
+        } else {
+            if !self.fixed_seed {
+                self.user_seed = Seed::from_random(&mut self.gen.rnd);
+            }
+            self.retry_attempt = 0;
+            self.original_seed = self.user_seed.clone();
+        }
+
+        let builder = self.get_builder();
+        let gen = builder.build(&self.user_seed)?;
+
+        // random layouts are derived from the user seed and auto fit shifts the waypoints and
+        // derives the map size from them, so show the map config that is used
+        if self.random_layout || self.map_config.auto_fit {
+            self.map_config = builder.get_fitted_map_config(&self.user_seed)?;
         }
 
-        self.gen = Generator::new(
-            &self.gen_config,
-            &self.map_config,
-            &self.thm_config,
-            self.user_seed.clone(),
-        );
+        self.post_runner = None;
+        self.gen = gen;
 
         // reset debug layers, if used
         if !self.disable_debug_layers {
             self.initialize_debug_layers();
         }
+
+        self.timeline.clear();
+        if self.use_snapshots {
+            self.timeline.record(&self.gen, &self.post_runner);
+        }
+
+        Ok(())
+    }
+
+    /// Switches into setup mode. If enabled and allowed by the retry policy, the generation is
+    /// restarted with the seed of the next attempt.
+    pub fn on_generation_failed(&mut self, failure: AttemptFailure) {
+        warn!(
+            "attempt {} (seed {}) failed: {:?} {}",
+            self.retry_attempt,
+            self.user_seed.to_base64(),
+            failure.kind,
+            failure.error
+        );
+        self.set_setup();
+
+        if self.retry_on_failure && self.retry_policy.should_retry(&failure, self.retry_attempt) {
+            self.retry_attempt += 1;
+            self.retry_seed = Some(RetryPolicy::get_attempt_seed(
+                &self.original_seed,
+                self.retry_attempt,
+            ));
+            self.set_playing();
+        }
+    }
+
+    /// Restores the generator state of a snapshot and pauses the generation, so it can be
+    /// continued from there.
+    pub fn restore_snapshot(&mut self, index: usize) {
+        let Some(snapshot) = self.timeline.restore(index) else {
+            return;
+        };
+
+        self.gen = snapshot.gen.clone();
+        self.post_runner = snapshot.post_runner.clone();
+        self.set_stopped();
+        self.restore_lock_layers();
+    }
+
+    /// debug layers are not part of snapshots or saved states, so at least restore the locks
+    fn restore_lock_layers(&mut self) {
+        if let Some(debug_layers) = self.debug_layers.as_mut() {
+            *debug_layers.bool_grid("lock") = self.gen.walker.locked_positions.clone();
+            *debug_layers.bool_grid("waypoint_lock") =
+                self.gen.walker.locked_waypoint_positions.clone();
+        }
+    }
+
+    /// saves the current generation, so it can be resumed later
+    pub fn save_state(&self, path: &str) -> Result<(), &'static str> {
+        if self.post_runner.is_some() {
+            return Err("state can only be saved before post processing");
+        }
+
+        GenerationState {
+            version: crate_version!().to_string(),
+            gen_config: self.gen_config.clone(),
+            map_config: self.map_config.clone(),
+            thm_config: self.thm_config.clone(),
+            gen: self.gen.clone(),
+        }
+        .save(path)
+    }
+
+    /// loads a saved generation and pauses, so it can be resumed
+    pub fn load_state(&mut self, path: &str) -> Result<(), &'static str> {
+        let state = GenerationState::load(path)?;
+
+        self.gen_config = state.gen_config;
+        self.map_config = state.map_config;
+        self.thm_config = state.thm_config;
+        self.gen = state.gen;
+        self.user_seed = self.gen.rnd.seed.clone();
+        self.post_runner = None;
+        self.set_stopped();
+
+        if !self.disable_debug_layers {
+            self.initialize_debug_layers();
+            self.restore_lock_layers();
+        }
+
+        self.timeline.clear();
+        if self.use_snapshots {
+            self.timeline.record(&self.gen, &self.post_runner);
+        }
+
+        Ok(())
+    }
+
+    /// Performs up to the given amount of walker steps, stops early once the walker is finished
+    /// or the generation is paused.
+    pub fn step_walker(&mut self, steps: usize) {
+        for _ in 0..steps {
+            if self.is_paused() || self.gen.walker.finished {
+                break;
+            }
+
+            match self
+                .gen
+                .step(&self.gen_config, true, &mut self.debug_layers)
+            {
+                Ok(()) => {
+                    if self.use_snapshots {
+                        self.timeline.record_step(&self.gen);
+                    }
+                }
+                Err(err) => {
+                    self.on_generation_failed(AttemptFailure::new(FailureKind::Walker, err));
+                }
+            }
+
+            // walker did a step using SingleStep -> now pause
+            if self.is_single_setp() {
+                self.set_stopped();
+            }
+        }
+    }
+
+    /// Once the walker is finished, performs post processing and export preprocessing and
+    /// checks the map against the acceptance criteria. When single stepping, only one post
+    /// processing stage is performed. Returns whether the map was finished successfully.
+    pub fn step_post_processing(&mut self) -> bool {
+        // TODO: handling successfull generation via 'setup' state is kinda stupid, i should
+        // just add a new state variable for this, in the generator?
+        if !self.gen.walker.finished || !self.is_playing() {
+            return false;
+        }
+
+        let single_stage = self.is_single_setp();
+
+        // kinda crappy, but ensure that even a panic doesnt crash the program
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.post_process(single_stage)));
+
+        match result {
+            // more stages left, wait for next step
+            Ok(Ok(false)) => self.set_stopped(),
+            Ok(Ok(true)) => {
+                let export = if self.export_preprocess {
+                    panic::catch_unwind(AssertUnwindSafe(|| {
+                        self.gen.export_preprocess(
+                            &self.thm_config,
+                            &mut self.debug_layers,
+                            self.verbose_post_process,
+                        )
+                    }))
+                    .map(Some)
+                } else {
+                    Ok(None)
+                };
+
+                // switch into setup mode for next map, or retry if the map is rejected
+                match export.map(|export| self.check_acceptance(export.as_ref())) {
+                    Ok(Ok(())) => {
+                        self.set_setup();
+                        return true;
+                    }
+                    Ok(Err(err)) => {
+                        self.on_generation_failed(AttemptFailure::new(FailureKind::Rejected, err))
+                    }
+                    Err(_) => self.on_generation_failed(AttemptFailure::new(
+                        FailureKind::Panic,
+                        "export preprocessing panicked",
+                    )),
+                }
+            }
+            Ok(Err(err)) => {
+                self.on_generation_failed(AttemptFailure::new(FailureKind::PostProcessing, err))
+            }
+            Err(_) => self.on_generation_failed(AttemptFailure::new(
+                FailureKind::Panic,
+                "generation panicked",
+            )),
+        }
+
+        false
+    }
+
+    /// Performs the next post processing stage, or all remaining stages. Returns whether post
+    /// processing is finished.
+    pub fn post_process(&mut self, single_stage: bool) -> Result<bool, &'static str> {
+        loop {
+            let runner = self.post_runner.get_or_insert_with(PipelineRunner::default);
+            let Some((stage, duration)) = runner.step(
+                &mut self.gen,
+                &self.gen_config,
+                &self.thm_config,
+                &mut self.debug_layers,
+            )?
+            else {
+                return Ok(true);
+            };
+            let is_finished = runner.is_finished(&self.gen_config);
+
+            if self.verbose_post_process {
+                println!("{}: {:?}", stage.name(), duration);
+            }
+
+            if self.use_snapshots {
+                self.timeline.record(&self.gen, &self.post_runner);
+            }
+
+            if single_stage {
+                return Ok(is_finished);
+            }
+        }
+    }
+
+    /// Checks the finished map against the acceptance criteria of the generation config. The
+    /// export transform has to be passed if export preprocessing was performed.
+    pub fn check_acceptance(&self, export: Option<&ExportTransform>) -> Result<(), &'static str> {
+        if self.gen_config.acceptance.is_empty() {
+            return Ok(());
+        }
+
+        let runner = self
+            .post_runner
+            .as_ref()
+            .ok_or("post processing was not performed")?;
+        let mut report = GenerationReport::new(&self.gen, &self.map_config, &runner.ctx);
+        if let Some(export) = export {
+            report.apply_export(export, &self.gen.map);
+        }
+
+        self.gen_config.acceptance.check_map(&self.gen.map, &report)
     }
 
     fn mouse_in_viewport(cam: &Camera2D) -> bool {
@@ -363,6 +674,27 @@ impl Editor {
         }
     }
 
+    pub fn save_state_dialog(&mut self) {
+        let cwd = env::current_dir().unwrap();
+        let initial_path = cwd.join("state.json").to_string_lossy().to_string();
+        if let Some(path_out) = tinyfiledialogs::save_file_dialog("save state", &initial_path) {
+            self.save_state(&path_out).unwrap_or_else(|err| {
+                warn!("couldn't save state: {}", err);
+            });
+        }
+    }
+
+    pub fn load_state_dialog(&mut self) {
+        let cwd = env::current_dir().unwrap();
+        let initial_path = cwd.to_string_lossy().to_string();
+        if let Some(path_in) = tinyfiledialogs::open_file_dialog("load state", &initial_path, None)
+        {
+            self.load_state(&path_in).unwrap_or_else(|err| {
+                warn!("couldn't load state: {}", err);
+            });
+        }
+    }
+
     pub fn handle_user_inputs(&mut self) {
         is_key_pressed(KeyCode::LeftShift);
 
@@ -1,14 +1,18 @@
 use clap::crate_version;
+use serde::{Deserialize, Serialize};
 use timing::Timer;
 
 use crate::{
-    config::{GenerationConfig, MapConfig, ThemeConfig},
+    builder::GeneratorBuilder,
+    config::{DifficultyCurve, GenerationConfig, MapConfig, ThemeConfig, WaypointOverride},
     debug::DebugLayers,
     kernel::Kernel,
     map::{BlockType, Map, Overwrite},
+    pipeline::PipelineRunner,
     position::Position,
-    post_processing::{self as post, flood_fill},
+    post_processing as post,
     random::{Random, Seed},
+    transform::{ExportTransform, MapTransform},
     utils::safe_slice_mut,
     walker::CuteWalker,
 };
@@ -20,6 +24,7 @@ pub fn print_time(timer: &mut Timer, message: &str, print: bool) {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Generator {
     pub walker: CuteWalker,
     pub map: Map,
@@ -28,17 +33,50 @@ pub struct Generator {
     pub rnd: Random,
 
     /// remember where generation began, so a start room can be placed in post processing
-    spawn: Position,
+    pub spawn: Position,
+
+    /// waypoint overrides for each walker (sub-)waypoint, empty if map has no overrides
+    waypoint_overrides: Vec<Option<WaypointOverride>>,
+
+    /// optional curve that interpolates the generation config along the map
+    difficulty_curve: Option<DifficultyCurve>,
+
+    /// generation config with difficulty curve and overrides of the current waypoint applied,
+    /// if there are any
+    active_config: Option<GenerationConfig>,
 }
 
 impl Generator {
-    /// derive an initial generator state based on a GenerationConfig
+    /// derive an initial generator state based on a GenerationConfig. Panics if an override of
+    /// the first waypoint results in an invalid config.
     pub fn new(
         gen_config: &GenerationConfig,
         map_config: &MapConfig,
         thm_config: &ThemeConfig,
         seed: Seed,
     ) -> Generator {
+        Generator::new_with_curve(gen_config, map_config, thm_config, seed, None)
+            .expect("invalid waypoint override, see MapConfig::validate")
+    }
+
+    /// derive an initial generator state with an optional difficulty curve. The walker settings
+    /// are then taken from the curve, while the base config is still used for everything else
+    /// (e.g. subwaypoints and post processing).
+    pub fn new_with_curve(
+        gen_config: &GenerationConfig,
+        map_config: &MapConfig,
+        thm_config: &ThemeConfig,
+        seed: Seed,
+        difficulty_curve: Option<DifficultyCurve>,
+    ) -> Result<Generator, &'static str> {
+        let lock_size = Generator::get_lock_size(gen_config, map_config, &difficulty_curve);
+        let map_config = &Generator::get_fitted_map_config(
+            gen_config,
+            map_config,
+            thm_config,
+            &difficulty_curve,
+        );
+
         let map = Map::new(map_config.width, map_config.height, BlockType::Hookable);
         let spawn = map_config.waypoints.first().unwrap().clone();
         let mut rnd = Random::new(seed, gen_config);
@@ -47,12 +85,36 @@ impl Generator {
             Generator::generate_sub_waypoints(&map_config.waypoints, gen_config, &mut rnd)
                 .unwrap_or(map_config.waypoints.clone()); // on failure just use initial waypoints
 
+        // map overrides of the initial waypoints to the derived subwaypoints
+        let waypoint_overrides = if map_config.waypoint_overrides.is_empty() {
+            Vec::new()
+        } else {
+            Generator::get_sub_waypoint_origins(&map_config.waypoints, gen_config)
+                .unwrap_or((0..map_config.waypoints.len()).collect())
+                .iter()
+                .map(|origin| map_config.waypoint_overrides.get(origin).cloned())
+                .collect()
+        };
+
+        // resolve the config of the first waypoint, so its overrides already apply to the
+        // initial kernels
+        let active_config = Generator::get_active_config(
+            &waypoint_overrides,
+            &difficulty_curve,
+            0,
+            0.0,
+            gen_config,
+        )?;
+        if let Some(active_config) = &active_config {
+            rnd.update_dists(active_config);
+        }
+
         // initialize walker
         let inner_kernel_size = rnd.sample_inner_kernel_size();
         let outer_kernel_size = inner_kernel_size + rnd.sample_outer_kernel_margin();
         let inner_kernel = Kernel::new(inner_kernel_size, 0.0);
         let outer_kernel = Kernel::new(outer_kernel_size, 0.0);
-        let walker = CuteWalker::new(
+        let mut walker = CuteWalker::new(
             spawn.clone(),
             inner_kernel,
             outer_kernel,
@@ -60,16 +122,20 @@ impl Generator {
             &map,
             gen_config,
         );
+        walker.lock_size = lock_size;
 
         let mut gen = Generator {
             walker,
             map,
             rnd,
             spawn,
+            waypoint_overrides,
+            difficulty_curve,
+            active_config,
         };
 
         gen.preprocessing(thm_config).unwrap(); // TODO: move somewhere else + pass
-        gen
+        Ok(gen)
     }
 
     pub fn preprocessing(&mut self, thm_config: &ThemeConfig) -> Result<(), &'static str> {
@@ -249,6 +315,95 @@ impl Generator {
         );
     }
 
+    /// overrides and difficulty curve can use larger kernels, so locking has to consider those
+    /// as well
+    fn get_lock_size(
+        gen_config: &GenerationConfig,
+        map_config: &MapConfig,
+        difficulty_curve: &Option<DifficultyCurve>,
+    ) -> usize {
+        let mut lock_size = gen_config.get_lock_size();
+        for waypoint_override in map_config.waypoint_overrides.values() {
+            lock_size = lock_size.max(waypoint_override.apply(gen_config).get_lock_size());
+        }
+        if let Some(difficulty_curve) = difficulty_curve {
+            lock_size = lock_size.max(difficulty_curve.get_lock_size());
+        }
+
+        lock_size
+    }
+
+    /// map config that is used for generation, if enabled the map size is derived from the
+    /// waypoints, see MapConfig::fit_to_waypoints
+    pub fn get_fitted_map_config(
+        gen_config: &GenerationConfig,
+        map_config: &MapConfig,
+        thm_config: &ThemeConfig,
+        difficulty_curve: &Option<DifficultyCurve>,
+    ) -> MapConfig {
+        let mut map_config = map_config.clone();
+        if map_config.auto_fit {
+            let lock_size = Generator::get_lock_size(gen_config, &map_config, difficulty_curve);
+            map_config.fit_to_waypoints(gen_config, thm_config, lock_size);
+        }
+
+        map_config
+    }
+
+    /// config with the difficulty curve and the overrides of the given waypoint applied, None if
+    /// neither overrides nor a difficulty curve are used. Interpolated configs are validated
+    /// here, so that the editor and the builder fail the same way.
+    fn get_active_config(
+        waypoint_overrides: &[Option<WaypointOverride>],
+        difficulty_curve: &Option<DifficultyCurve>,
+        goal_index: usize,
+        progress: f32,
+        gen_config: &GenerationConfig,
+    ) -> Result<Option<GenerationConfig>, &'static str> {
+        let curve_config = difficulty_curve
+            .as_ref()
+            .map(|curve| curve.get_config(progress))
+            .transpose()?;
+
+        let waypoint_override = waypoint_overrides
+            .get(goal_index)
+            .and_then(|waypoint_override| waypoint_override.as_ref());
+
+        let active_config = match (curve_config, waypoint_override) {
+            (Some(curve_config), Some(waypoint_override)) => {
+                Some(waypoint_override.apply(&curve_config))
+            }
+            (Some(curve_config), None) => Some(curve_config),
+            (None, Some(waypoint_override)) => Some(waypoint_override.apply(gen_config)),
+            (None, None) => None,
+        };
+
+        if let Some(active_config) = &active_config {
+            active_config.validate()?;
+        }
+
+        Ok(active_config)
+    }
+
+    fn update_active_config(&mut self, gen_config: &GenerationConfig) -> Result<(), &'static str> {
+        if self.waypoint_overrides.is_empty() && self.difficulty_curve.is_none() {
+            return Ok(());
+        }
+
+        self.active_config = Generator::get_active_config(
+            &self.waypoint_overrides,
+            &self.difficulty_curve,
+            self.walker.goal_index,
+            self.walker.get_progress(),
+            gen_config,
+        )?;
+
+        self.rnd
+            .update_dists(self.active_config.as_ref().unwrap_or(gen_config));
+
+        Ok(())
+    }
+
     /// perform one step of the map generation
     pub fn step(
         &mut self,
@@ -256,12 +411,14 @@ impl Generator {
         validate: bool,
         debug_layers: &mut Option<DebugLayers>,
     ) -> Result<(), &'static str> {
+        let waypoint_reached_dist = self
+            .active_config
+            .as_ref()
+            .unwrap_or(gen_config)
+            .waypoint_reached_dist;
+
         // check if walker has reached currernt goal position
-        if self
-            .walker
-            .is_goal_reached(&gen_config.waypoint_reached_dist)
-            == Some(true)
-        {
+        if self.walker.is_goal_reached(&waypoint_reached_dist) == Some(true) {
             // get next waypoint
             self.walker.next_waypoint();
 
@@ -278,6 +435,9 @@ impl Generator {
                 }
             }
 
+            // update difficulty curve and overrides for next waypoint
+            self.update_active_config(gen_config)?;
+
             // lock all other waypoints
             if gen_config.waypoint_lock_distance > 0 {
                 self.walker.update_waypoint_locks(
@@ -293,6 +453,9 @@ impl Generator {
             return Ok(());
         }
 
+        // from here on, use the config of the current waypoint
+        let gen_config = self.active_config.as_ref().unwrap_or(gen_config);
+
         if validate {
             gen_config.validate()?;
         }
@@ -333,9 +496,7 @@ impl Generator {
 
         // iterate over all neighboring pairs of global waypoints
         for (p1, p2) in waypoints.windows(2).map(|w| (&w[0], &w[1])) {
-            let distance = p1.distance(p2);
-            let num_subwaypoints =
-                ((distance / gen_config.max_subwaypoint_dist).floor() as usize).max(1);
+            let num_subwaypoints = Generator::get_sub_waypoint_count(p1, p2, gen_config);
 
             for subwaypoint_index in 0..num_subwaypoints {
                 let lerp_weight = (subwaypoint_index as f32) / (num_subwaypoints as f32);
@@ -356,6 +517,40 @@ impl Generator {
         Some(subwaypoints)
     }
 
+    fn get_sub_waypoint_count(
+        p1: &Position,
+        p2: &Position,
+        gen_config: &GenerationConfig,
+    ) -> usize {
+        let distance = p1.distance(p2);
+        ((distance / gen_config.max_subwaypoint_dist).floor() as usize).max(1)
+    }
+
+    /// For each subwaypoint generated by `generate_sub_waypoints`, determine the index of the
+    /// initial waypoint it leads towards. The first subwaypoint between two waypoints is placed
+    /// at the first waypoint, so it belongs to that one, all others belong to the second one.
+    pub fn get_sub_waypoint_origins(
+        waypoints: &[Position],
+        gen_config: &GenerationConfig,
+    ) -> Option<Vec<usize>> {
+        if gen_config.max_subwaypoint_dist <= 0.0 {
+            return None;
+        }
+
+        let mut origins: Vec<usize> = Vec::new();
+
+        for (index, (p1, p2)) in waypoints.windows(2).map(|w| (&w[0], &w[1])).enumerate() {
+            let num_subwaypoints = Generator::get_sub_waypoint_count(p1, p2, gen_config);
+            origins.push(index);
+            origins.extend((1..num_subwaypoints).map(|_| index + 1));
+        }
+
+        // last point
+        origins.push(waypoints.len() - 1);
+
+        Some(origins)
+    }
+
     pub fn perform_all_post_processing(
         &mut self,
         gen_config: &GenerationConfig,
@@ -364,152 +559,90 @@ impl Generator {
         verbose: bool,
     ) -> Result<(), &'static str> {
         let mut timer = Timer::start();
+        let mut runner = PipelineRunner::default();
 
-        self.generate_spawn(thm_config);
-        print_time(&mut timer, "place start room", verbose);
-
-        if gen_config.min_freeze_size > 0 {
-            // TODO: Maybe add some alternative function for the case of min_freeze_size=1
-            post::remove_freeze_blobs(self, gen_config.min_freeze_size, debug_layers);
-            print_time(&mut timer, "detect blobs", verbose);
+        while let Some((stage, _)) = runner.step(self, gen_config, thm_config, debug_layers)? {
+            print_time(&mut timer, stage.name(), verbose);
         }
 
-        let ff = flood_fill(self, &[self.spawn.clone()], Some(&self.walker.pos), false)?;
-        print_time(&mut timer, "flood fill", verbose);
-
-        // we do expanding edge bugs after determining ff, because otherwise it might overlap end
-        // position with new padded freeze.. idk if i like this order tho because many freeze
-        // blocks will now have a ff distance?
-        let edge_bugs = post::fix_edge_bugs_expanding(self).expect("fix edge bugs failed");
-        print_time(&mut timer, "fix edge bugs", verbose);
-
-        post::generate_finish_room(
-            &self.walker.pos.clone(),
-            &mut self.map,
-            &self.walker.locked_positions,
-            &ff.distance,
-            4,
-        )?;
-        print_time(&mut timer, "place finish room", verbose);
-
-        // lock all remaining blocks
-        self.walker
-            .lock_previous_location(&self.map, gen_config, true)?;
-        print_time(&mut timer, "finish walker lock", verbose);
+        Ok(())
+    }
 
-        let ff_main_path = flood_fill(self, ff.path.as_ref().unwrap(), None, true)?;
-        print_time(&mut timer, "flood fill (main path dist)", verbose);
+    /// Transforms map, walker, spawn and debug layers consistently. Random has to be resolved
+    /// first, see MapTransform::resolve. Post processing results are not transformed.
+    pub fn transform(&mut self, transform: &MapTransform, debug_layers: &mut Option<DebugLayers>) {
+        let (width, height) = (self.map.width, self.map.height);
 
+        self.map.transform(transform);
+        self.walker.transform(transform, width, height);
+        self.spawn = transform.apply_pos(&self.spawn, width, height);
         if let Some(debug_layers) = debug_layers {
-            debug_layers
-                .float_layers
-                .get_mut("flood_fill")
-                .unwrap()
-                .grid = ff.distance.map(|v| v.map(|v| v as f32));
-            if let Some(path) = ff.path.as_ref() {
-                let path_grid = &mut debug_layers.bool_layers.get_mut("path").unwrap().grid;
-                for pos in path {
-                    path_grid[pos.as_index()] = true;
-                }
-            }
-        }
-
-        // fill up dead ends
-        if gen_config.use_dead_end_removal {
-            let dead_end_blocks =
-                post::fill_dead_ends(&mut self.map, gen_config, &ff_main_path.distance)?;
-            print_time(&mut timer, "fill dead ends", verbose);
-
-            // fix stair artifacts resulting from dead end filling
-            post::fix_stairs(&mut self.map, dead_end_blocks, &mut self.rnd);
-            print_time(&mut timer, "fix stairs", verbose);
+            debug_layers.transform(transform);
         }
+    }
 
-        // TODO: only perform this for updated blocks?
-        post::fix_edge_bugs_expanding(self).expect("fix edge bugs failed");
-        print_time(&mut timer, "fix edge_bugs #2", verbose);
-
-        post::generate_all_skips(
-            self,
-            gen_config.skip_length_bounds,
-            gen_config.skip_min_spacing_sqr,
-            gen_config.max_level_skip,
-            &ff.distance,
-            debug_layers,
+    /// Crops the map like Map::crop_to_margin and moves walker, spawn and debug layers into
+    /// the cropped map. Returns the top left position of the cropped area.
+    pub fn crop(
+        &mut self,
+        margin: usize,
+        debug_layers: &mut Option<DebugLayers>,
+    ) -> Option<Position> {
+        let top_left = self.map.crop_to_margin(margin)?;
+        let (width, height) = (self.map.width, self.map.height);
+
+        self.walker.crop(&top_left, width, height);
+        self.spawn = Position::new(
+            self.spawn.x.saturating_sub(top_left.x).min(width - 1),
+            self.spawn.y.saturating_sub(top_left.y).min(height - 1),
         );
-        print_time(&mut timer, "generate skips", verbose);
-
-        let ff_map_length =
-            ff.distance[self.walker.pos.as_index()].expect("cant determine map length");
-
-        // platforms
-        let floor_pos = post::generate_platforms(
-            &mut self.map,
-            gen_config,
-            &ff.distance,
-            ff_map_length,
-            debug_layers,
-        )?;
-        print_time(&mut timer, "generate platforms", verbose);
-
-        post::fill_open_areas(self, &gen_config.max_distance, debug_layers);
-        print_time(&mut timer, "place obstacles", verbose);
-
-        // post::remove_unused_blocks(&mut self.map, &self.walker.locked_positions);
-
-        // do final ff run to ensure there is a playable path to finish
-        let ff_final = flood_fill(self, &[self.spawn.clone()], None, false)?;
-        let end_distance = ff_final.distance[self.walker.pos.as_index()];
-        if end_distance.is_none() {
-            return Err("No valid path to finish");
-        }
-        print_time(&mut timer, "map path validation", verbose);
-
         if let Some(debug_layers) = debug_layers {
-            debug_layers
-                .float_layers
-                .get_mut("main_path_dist")
-                .unwrap()
-                .grid = ff_main_path.distance.map(|v| v.map(|v| v as f32));
-            debug_layers.bool_layers.get_mut("lock").unwrap().grid =
-                self.walker.locked_positions.clone();
-            debug_layers.bool_layers.get_mut("edge_bugs").unwrap().grid = edge_bugs;
-
-            let grid = &mut debug_layers.bool_layers.get_mut("floor").unwrap().grid;
-
-            // floor
-            for floor_pos in floor_pos {
-                grid[floor_pos.pos.as_index()] = true;
-            }
+            debug_layers.crop(&top_left, width, height);
         }
-        print_time(&mut timer, "set debug layers", verbose);
 
-        Ok(())
+        Some(top_left)
     }
 
     /// Perform preprocessing steps that are intended for map export, this call can be skipped
-    /// if the generated maps are not intended to be exported
+    /// if the generated maps are not intended to be exported. Returns the applied transform
+    /// and crop.
     pub fn export_preprocess(
         &mut self,
         thm_config: &ThemeConfig,
         debug_layers: &mut Option<DebugLayers>,
         verbose: bool,
-    ) {
+    ) -> ExportTransform {
         let mut timer = Timer::start();
+        let (width, height) = (self.map.width, self.map.height);
+
+        // transform before generating noise, as overlay noise depends on it
+        let transform = thm_config.transform.resolve(&mut self.rnd);
+        if transform != MapTransform::None {
+            self.transform(&transform, debug_layers);
+            print_time(&mut timer, "transform map", verbose);
+        }
 
-        // flip before generating noise, as overlay noise depends on it
-        if self.rnd.get_bool_with_prob(0.5) {
-            self.map.flip_x_axis();
-            print_time(&mut timer, "flip map", verbose);
+        // crop before generating noise, so noise layers match the final map size
+        let mut crop_offset = None;
+        if let Some(crop_margin) = thm_config.crop_margin {
+            crop_offset = self.crop(crop_margin, debug_layers);
+            print_time(&mut timer, "crop map", verbose);
         }
 
         post::generate_noise_layers(&mut self.map, &mut self.rnd, thm_config, debug_layers);
         print_time(&mut timer, "generate noise layers", verbose);
+
+        ExportTransform {
+            transform,
+            width,
+            height,
+            crop_offset,
+        }
     }
 
-    /// Generates an entire map with a single function call. This function is used by the CLI.
-    /// It is important to keep this function up to date with the editor generation, so that
-    /// fixed seed map generations result in the same map.
+    /// Generates an entire map with a single function call, see GeneratorBuilder for more
+    /// options like difficulty curves. Unlike GeneratorBuilder::generate, panics are propagated
+    /// to the caller.
     pub fn generate_map(
         max_steps: usize,
         seed: &Seed,
@@ -518,27 +651,16 @@ impl Generator {
         thm_config: &ThemeConfig,
         export_preprocess: bool,
     ) -> Result<Map, &'static str> {
-        let mut gen = Generator::new(gen_config, map_config, thm_config, seed.clone());
-
-        // validate config
-        gen_config.validate()?;
-
-        // perform all walker steps, skip further validation/debugging
-        for _ in 0..max_steps {
-            if gen.walker.finished {
-                break;
-            }
-            gen.step(gen_config, false, &mut None)?;
-        }
-
-        // perform all post processing step without creating any debug layers
-        gen.perform_all_post_processing(gen_config, thm_config, &mut None, false)?;
-
-        // if enabled, perform all export preprocessing steps without debug layers
-        if export_preprocess {
-            gen.export_preprocess(thm_config, &mut None, false);
-        }
-
-        Ok(gen.map)
+        GeneratorBuilder::new()
+            .with_gen_config(gen_config.clone())
+            .with_map_config(map_config.clone())
+            .with_theme_config(thm_config.clone())
+            .with_seed(seed.clone())
+            .with_max_steps(max_steps)
+            .with_export_preprocess(export_preprocess)
+            .with_catch_panics(false)
+            .generate()
+            .map(|result| result.map)
+            .map_err(|failure| failure.error())
     }
 }
@@ -0,0 +1,157 @@
+use std::{fs, panic};
+
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::EditorArgs,
+    builder::GeneratorBuilder,
+    config::{GenerationConfig, ThemeConfig},
+    editor::Editor,
+    map::Map,
+    random::Seed,
+};
+
+/// golden hashes, relative to the crate root
+pub const GOLDEN_HASHES_PATH: &str = "data/golden_hashes.json";
+
+const GOLDEN_MAP_CONFIGS: [&str; 2] = ["small_s_tight", "climb"];
+const GOLDEN_SEEDS: [u64; 2] = [0, 1];
+const GOLDEN_MAX_STEPS: usize = 200_000;
+
+/// Fixed generation input with the expected map hash. A failed generation is expected to fail
+/// again, so its hash is None.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GoldenCase {
+    pub gen_config: String,
+    pub map_config: String,
+    pub seed: u64,
+    pub hash: Option<u64>,
+}
+
+/// hashes of a golden case using both the one-shot and the editor generation
+#[derive(Debug, Clone)]
+pub struct GoldenResult {
+    pub case: GoldenCase,
+    pub one_shot_hash: Option<u64>,
+    pub editor_hash: Option<u64>,
+}
+
+impl GoldenResult {
+    pub fn is_deterministic(&self) -> bool {
+        self.one_shot_hash == self.editor_hash
+    }
+
+    pub fn matches_golden(&self) -> bool {
+        self.is_deterministic() && self.one_shot_hash == self.case.hash
+    }
+}
+
+impl GoldenCase {
+    pub fn get_builder(&self) -> Result<GeneratorBuilder, &'static str> {
+        Ok(GeneratorBuilder::new()
+            .with_gen_preset(&self.gen_config)?
+            .with_map_preset(&self.map_config)?
+            .with_theme_config(ThemeConfig::default())
+            .with_seed(Seed::from_u64(self.seed))
+            .with_max_steps(GOLDEN_MAX_STEPS)
+            .with_export_preprocess(true))
+    }
+
+    /// generates the map of this case using the builder and the editor generation
+    pub fn check(&self) -> GoldenResult {
+        GoldenResult {
+            case: self.clone(),
+            one_shot_hash: self.get_hash(generate_one_shot),
+            editor_hash: self.get_hash(generate_editor),
+        }
+    }
+
+    fn get_hash(
+        &self,
+        generate: fn(&GeneratorBuilder) -> Result<Map, &'static str>,
+    ) -> Option<u64> {
+        let builder = self.get_builder().ok()?;
+
+        // panics are just treated as failed generation
+        panic::catch_unwind(|| generate(&builder))
+            .ok()?
+            .ok()
+            .map(|map| map.grid_hash())
+    }
+}
+
+fn generate_one_shot(builder: &GeneratorBuilder) -> Result<Map, &'static str> {
+    builder
+        .generate()
+        .map(|result| result.map)
+        .map_err(|failure| failure.error())
+}
+
+/// Generates the map like the editor does, using its walker and post processing steps. Every
+/// step is validated and debug layers and snapshots are used.
+fn generate_editor(builder: &GeneratorBuilder) -> Result<Map, &'static str> {
+    let mut editor = Editor::new(
+        builder.gen_config.clone(),
+        builder.map_config.clone(),
+        builder.thm_config.clone(),
+        &EditorArgs::parse_from(["editor"]),
+    );
+    editor.thm_config = builder.thm_config.clone();
+    editor.user_seed = builder.seed.clone();
+    editor.fixed_seed = true;
+    editor.export_preprocess = builder.export_preprocess;
+
+    editor.set_playing();
+    editor.step_walker(builder.max_steps);
+    if !editor.gen.walker.finished {
+        return Err("walker failed");
+    }
+
+    match editor.step_post_processing() {
+        true => Ok(editor.gen.map),
+        false => Err("post processing failed"),
+    }
+}
+
+/// all golden cases without hashes
+pub fn get_golden_cases() -> Vec<GoldenCase> {
+    let mut cases = Vec::new();
+
+    for gen_config in GenerationConfig::get_all_configs() {
+        for map_config in GOLDEN_MAP_CONFIGS {
+            for seed in GOLDEN_SEEDS {
+                cases.push(GoldenCase {
+                    gen_config: gen_config.name.clone(),
+                    map_config: map_config.to_string(),
+                    seed,
+                    hash: None,
+                });
+            }
+        }
+    }
+
+    cases
+}
+
+/// all golden cases with freshly generated hashes
+pub fn generate_golden_cases() -> Vec<GoldenCase> {
+    get_golden_cases()
+        .into_iter()
+        .map(|mut case| {
+            case.hash = case.get_hash(generate_one_shot);
+            case
+        })
+        .collect()
+}
+
+pub fn load_golden_cases(path: &str) -> Result<Vec<GoldenCase>, &'static str> {
+    let serialized = fs::read_to_string(path).map_err(|_| "failed to read golden hashes")?;
+    serde_json::from_str(&serialized).map_err(|_| "failed to deserialize golden hashes")
+}
+
+pub fn save_golden_cases(path: &str, cases: &[GoldenCase]) -> Result<(), &'static str> {
+    let serialized =
+        serde_json::to_string_pretty(cases).map_err(|_| "failed to serialize golden hashes")?;
+    fs::write(path, serialized).map_err(|_| "failed to write golden hashes")
+}
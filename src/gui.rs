@@ -5,8 +5,10 @@ use tinyfiledialogs;
 
 use crate::{
     editor::{window_frame, Editor, SeedType},
+    layout::LayoutShape,
     position::{Position, ShiftDirection},
     random::{RandomDistConfig, Seed},
+    transform::MapTransform,
 };
 use egui::Context;
 use egui::{CollapsingHeader, Label, Ui};
@@ -223,6 +225,30 @@ pub fn edit_bool(ui: &mut Ui, value: &mut bool) {
     ui.add(egui::Checkbox::new(value, ""));
 }
 
+/// checkbox to enable the value, the value is only editable if enabled
+pub fn edit_opt_usize(ui: &mut Ui, value: &mut Option<usize>) {
+    let mut enabled = value.is_some();
+    ui.add(egui::Checkbox::new(&mut enabled, ""));
+    match (enabled, value.as_mut()) {
+        (true, Some(value)) => edit_usize(ui, value),
+        (true, None) => *value = Some(0),
+        (false, _) => *value = None,
+    }
+}
+
+/// checkbox to enable the value, the value is only editable if enabled
+pub fn edit_opt_f32(ui: &mut Ui, value: &mut Option<f32>) {
+    let mut enabled = value.is_some();
+    ui.add(egui::Checkbox::new(&mut enabled, ""));
+    match (enabled, value.as_mut()) {
+        (true, Some(value)) => {
+            ui.add(egui::DragValue::new(value).speed(0.1));
+        }
+        (true, None) => *value = Some(0.0),
+        (false, _) => *value = None,
+    }
+}
+
 pub fn menu(ctx: &Context, editor: &mut Editor) {
     egui::TopBottomPanel::top("top_menu").show(ctx, |ui| {
         egui::menu::bar(ui, |ui| {
@@ -230,6 +256,12 @@ pub fn menu(ctx: &Context, editor: &mut Editor) {
                 if ui.button("Save Map").clicked() {
                     editor.save_map_dialog();
                 }
+                if ui.button("Save State").clicked() {
+                    editor.save_state_dialog();
+                }
+                if ui.button("Load State").clicked() {
+                    editor.load_state_dialog();
+                }
                 if ui.button("Exit").clicked() {
                     exit(0)
                 }
@@ -297,9 +329,49 @@ pub fn sidebar(ctx: &Context, editor: &mut Editor) {
                 ui.checkbox(&mut editor.instant, "instant");
                 ui.checkbox(&mut editor.auto_generate, "auto generate");
                 ui.checkbox(&mut editor.retry_on_failure, "retry fail");
+                if editor.retry_attempt > 0 {
+                    ui.label(format!("attempt {}", editor.retry_attempt));
+                }
             });
         });
 
+        // =======================================[ POST PROCESSING ]===================================
+        if let Some(runner) = &editor.post_runner {
+            CollapsingHeader::new("Post Processing Stages")
+                .default_open(true)
+                .show(ui, |ui| {
+                    for (stage, duration) in runner.timings.iter() {
+                        ui.label(
+                            RichText::new(format!("{:<28} {:>10.2?}", stage.name(), duration))
+                                .monospace(),
+                        );
+                    }
+                    if let Some(next_stage) = runner.get_next_stage(&editor.gen_config) {
+                        ui.label(RichText::new(format!("next: {}", next_stage.name())).monospace());
+                    }
+                });
+        }
+
+        // =======================================[ TIMELINE ]===================================
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut editor.use_snapshots, "snapshots");
+
+            let snapshot_count = editor.timeline.snapshots.len();
+            if editor.use_snapshots && !editor.is_setup() && snapshot_count > 0 {
+                let current = editor.timeline.cursor.unwrap_or(snapshot_count - 1);
+                let mut selected = current;
+                ui.add(
+                    egui::Slider::new(&mut selected, 0..=snapshot_count - 1).custom_formatter(
+                        |index, _| editor.timeline.snapshots[index as usize].get_label(),
+                    ),
+                );
+
+                if selected != current {
+                    editor.restore_snapshot(selected);
+                }
+            }
+        });
+
         // =======================================[ SEED CONTROL ]===================================
         if editor.is_setup() {
             ui.separator();
@@ -417,6 +489,20 @@ pub fn sidebar(ctx: &Context, editor: &mut Editor) {
                     ui.selectable_value(&mut editor.gen_config, cfg.clone(), &cfg.name);
                 }
             });
+        ui.label("difficulty curve end:");
+        egui::ComboBox::from_id_source("curve_end_config")
+            .selected_text(
+                editor
+                    .curve_end_config
+                    .as_ref()
+                    .map_or("none".to_string(), |cfg| cfg.name.to_string()),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut editor.curve_end_config, None, "none");
+                for cfg in editor.init_gen_configs.iter() {
+                    ui.selectable_value(&mut editor.curve_end_config, Some(cfg.clone()), &cfg.name);
+                }
+            });
         ui.label("load map config:");
         egui::ComboBox::from_label(" ")
             .selected_text(editor.map_config.name.to_string())
@@ -430,8 +516,52 @@ pub fn sidebar(ctx: &Context, editor: &mut Editor) {
         ui.horizontal(|ui| {
             ui.checkbox(&mut editor.edit_gen_config, "edit gen");
             ui.checkbox(&mut editor.edit_map_config, "edit map");
+            ui.checkbox(&mut editor.random_layout, "random layout");
         });
 
+        // =======================================[ RANDOM LAYOUT ]===================================
+        if editor.random_layout {
+            egui::ComboBox::from_label("layout shape")
+                .selected_text(format!("{:?}", editor.layout_config.shape))
+                .show_ui(ui, |ui| {
+                    for shape in LayoutShape::all() {
+                        ui.selectable_value(
+                            &mut editor.layout_config.shape,
+                            shape,
+                            format!("{:?}", shape),
+                        );
+                    }
+                });
+            field_edit_widget(
+                ui,
+                &mut editor.layout_config.turns,
+                edit_usize,
+                "turns",
+                true,
+            );
+            field_edit_widget(
+                ui,
+                &mut editor.layout_config.width,
+                edit_usize,
+                "layout width",
+                true,
+            );
+            field_edit_widget(
+                ui,
+                &mut editor.layout_config.height,
+                edit_usize,
+                "layout height",
+                true,
+            );
+            field_edit_widget(
+                ui,
+                &mut editor.layout_config.min_waypoint_dist,
+                edit_f32_slider_bounded(0.0, 100.0),
+                "min waypoint dist",
+                true,
+            );
+        }
+
         egui::ScrollArea::vertical().show(ui, |ui| {
             // =======================================[ GENERATION CONFIG EDIT ]===================================
             if editor.edit_gen_config {
@@ -572,6 +702,22 @@ pub fn sidebar(ctx: &Context, editor: &mut Editor) {
                             "part width",
                             true,
                         );
+
+                        field_edit_widget(
+                            ui,
+                            &mut editor.gen_config.plat_min_gap_factor,
+                            edit_f32_slider_bounded(0.0, 1.0),
+                            "min gap factor",
+                            true,
+                        );
+
+                        field_edit_widget(
+                            ui,
+                            &mut editor.gen_config.plat_max_gap_factor,
+                            edit_f32_slider_bounded(1.0, 3.0),
+                            "max gap factor",
+                            true,
+                        );
                     });
 
                 CollapsingHeader::new("Momentum")
@@ -808,6 +954,75 @@ pub fn sidebar(ctx: &Context, editor: &mut Editor) {
                             );
                         });
                     });
+
+                CollapsingHeader::new("Post Processing")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        for pass in editor.gen_config.post_processing.iter_mut() {
+                            ui.checkbox(&mut pass.enabled, pass.stage.name());
+                        }
+                    });
+
+                CollapsingHeader::new("Forbidden Patterns")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let patterns = &mut editor.gen_config.forbidden_patterns;
+                        let mut removed = None;
+                        for (index, forbidden) in patterns.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(&forbidden.name);
+                                if ui.button("remove").clicked() {
+                                    removed = Some(index);
+                                }
+                            });
+                        }
+                        if let Some(index) = removed {
+                            patterns.remove(index);
+                        }
+                    });
+
+                CollapsingHeader::new("Acceptance Criteria")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let acceptance = &mut editor.gen_config.acceptance;
+                        for (value, label) in [
+                            (&mut acceptance.min_path_length, "min path length"),
+                            (&mut acceptance.max_path_length, "max path length"),
+                            (&mut acceptance.min_platform_gap, "min platform gap"),
+                            (&mut acceptance.max_platform_gap, "max platform gap"),
+                            (&mut acceptance.max_skip_count, "max skips"),
+                        ] {
+                            field_edit_widget(ui, value, edit_opt_usize, label, false);
+                        }
+                        field_edit_widget(
+                            ui,
+                            &mut acceptance.max_open_distance,
+                            edit_opt_f32,
+                            "max open distance",
+                            false,
+                        );
+                    });
+
+                CollapsingHeader::new("Guidance")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        field_edit_widget(
+                            ui,
+                            &mut editor.gen_config.use_guidance,
+                            edit_bool,
+                            "use guidance",
+                            false,
+                        );
+                        ui.add_enabled_ui(editor.gen_config.use_guidance, |ui| {
+                            field_edit_widget(
+                                ui,
+                                &mut editor.gen_config.guidance_cell_size,
+                                edit_usize,
+                                "cell size",
+                                false,
+                            );
+                        });
+                    });
             }
 
             // =======================================[ MAP CONFIG EDIT ]===================================
@@ -815,18 +1030,27 @@ pub fn sidebar(ctx: &Context, editor: &mut Editor) {
                 field_edit_widget(ui, &mut editor.map_config.name, edit_string, "name", false);
                 field_edit_widget(
                     ui,
-                    &mut editor.map_config.width,
-                    edit_usize,
-                    "map width",
-                    true,
-                );
-                field_edit_widget(
-                    ui,
-                    &mut editor.map_config.height,
-                    edit_usize,
-                    "map height",
-                    true,
+                    &mut editor.map_config.auto_fit,
+                    edit_bool,
+                    "auto fit",
+                    false,
                 );
+                ui.add_enabled_ui(!editor.map_config.auto_fit, |ui| {
+                    field_edit_widget(
+                        ui,
+                        &mut editor.map_config.width,
+                        edit_usize,
+                        "map width",
+                        true,
+                    );
+                    field_edit_widget(
+                        ui,
+                        &mut editor.map_config.height,
+                        edit_usize,
+                        "map height",
+                        true,
+                    );
+                });
                 ui.add_enabled_ui(editor.is_setup(), |ui| {
                     vec_edit_widget(
                         ui,
@@ -894,6 +1118,27 @@ pub fn theme_widget(ctx: &Context, editor: &mut Editor) {
                     "background nosie threshold",
                     false,
                 );
+
+                let mut crop_map = editor.thm_config.crop_margin.is_some();
+                field_edit_widget(ui, &mut crop_map, edit_bool, "crop map", false);
+                if crop_map {
+                    let crop_margin = editor.thm_config.crop_margin.get_or_insert(5);
+                    field_edit_widget(ui, crop_margin, edit_usize, "crop margin", false);
+                } else {
+                    editor.thm_config.crop_margin = None;
+                }
+
+                egui::ComboBox::from_label("transform")
+                    .selected_text(format!("{:?}", editor.thm_config.transform))
+                    .show_ui(ui, |ui| {
+                        for transform in MapTransform::all() {
+                            ui.selectable_value(
+                                &mut editor.thm_config.transform,
+                                transform,
+                                format!("{:?}", transform),
+                            );
+                        }
+                    });
             });
         });
 }
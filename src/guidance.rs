@@ -0,0 +1,179 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{debug::DebugLayers, map::Map, position::Position};
+
+/// how many path cells the guidance target is ahead of the walker
+const LOOKAHEAD_CELLS: usize = 2;
+
+/// Coarse path through unlocked space towards a goal. The path is found using A* on a
+/// downsampled lock grid, where each cell covers cell_size x cell_size blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Guidance {
+    pub cell_size: usize,
+
+    /// positions along the path, the last position is the goal itself
+    pub path: Vec<Position>,
+
+    /// index of the path position the walker is currently guided towards
+    pub target_index: usize,
+}
+
+impl Guidance {
+    /// Finds a coarse path from start to goal. A cell is considered blocked if the majority of
+    /// its positions is locked, the cells of start and goal are never blocked. Returns None if
+    /// goal is unreachable.
+    pub fn new<F>(
+        start: &Position,
+        goal: &Position,
+        map: &Map,
+        cell_size: usize,
+        is_locked: F,
+    ) -> Option<Guidance>
+    where
+        F: Fn(&Position) -> bool,
+    {
+        if !map.pos_in_bounds(start) || !map.pos_in_bounds(goal) {
+            return None;
+        }
+
+        let grid_width = map.width.div_ceil(cell_size);
+        let grid_height = map.height.div_ceil(cell_size);
+        let to_cell = |pos: &Position| (pos.x / cell_size, pos.y / cell_size);
+        let to_index = |(x, y): (usize, usize)| y * grid_width + x;
+
+        let start_cell = to_cell(start);
+        let goal_cell = to_cell(goal);
+
+        // blocked state is only determined for cells that are actually visited
+        let mut blocked: Vec<Option<bool>> = vec![None; grid_width * grid_height];
+        let mut is_blocked = |cell: (usize, usize)| -> bool {
+            *blocked[to_index(cell)].get_or_insert_with(|| {
+                let x_end = ((cell.0 + 1) * cell_size).min(map.width);
+                let y_end = ((cell.1 + 1) * cell_size).min(map.height);
+                let area = (x_end - cell.0 * cell_size) * (y_end - cell.1 * cell_size);
+
+                let mut locked_count = 0;
+                for x in (cell.0 * cell_size)..x_end {
+                    for y in (cell.1 * cell_size)..y_end {
+                        if is_locked(&Position::new(x, y)) {
+                            locked_count += 1;
+                        }
+                    }
+                }
+
+                locked_count * 2 > area
+            })
+        };
+
+        let heuristic = |(x, y): (usize, usize)| x.abs_diff(goal_cell.0) + y.abs_diff(goal_cell.1);
+
+        let mut cost = vec![usize::MAX; grid_width * grid_height];
+        let mut came_from = vec![usize::MAX; grid_width * grid_height];
+        let mut open = BinaryHeap::new();
+
+        cost[to_index(start_cell)] = 0;
+        open.push(Reverse((heuristic(start_cell), 0, start_cell)));
+
+        while let Some(Reverse((_, cell_cost, cell))) = open.pop() {
+            if cell == goal_cell {
+                break;
+            }
+
+            // skip outdated entries
+            if cell_cost > cost[to_index(cell)] {
+                continue;
+            }
+
+            let neighbors = [
+                (cell.0.checked_sub(1), Some(cell.1)),
+                (Some(cell.0 + 1), Some(cell.1)),
+                (Some(cell.0), cell.1.checked_sub(1)),
+                (Some(cell.0), Some(cell.1 + 1)),
+            ];
+
+            for neighbor in neighbors {
+                let (Some(x), Some(y)) = neighbor else {
+                    continue;
+                };
+                if x >= grid_width || y >= grid_height {
+                    continue;
+                }
+
+                let neighbor = (x, y);
+                if neighbor != goal_cell && is_blocked(neighbor) {
+                    continue;
+                }
+
+                let neighbor_cost = cell_cost + 1;
+                if neighbor_cost < cost[to_index(neighbor)] {
+                    cost[to_index(neighbor)] = neighbor_cost;
+                    came_from[to_index(neighbor)] = to_index(cell);
+                    open.push(Reverse((
+                        neighbor_cost + heuristic(neighbor),
+                        neighbor_cost,
+                        neighbor,
+                    )));
+                }
+            }
+        }
+
+        if cost[to_index(goal_cell)] == usize::MAX {
+            return None;
+        }
+
+        // reconstruct path from goal to start using cell centers, but exact goal position
+        let mut path = vec![goal.clone()];
+        let mut index = came_from[to_index(goal_cell)];
+        while index != usize::MAX && index != to_index(start_cell) {
+            let (x, y) = (index % grid_width, index / grid_width);
+            path.push(Position::new(
+                (x * cell_size + cell_size / 2).min(map.width - 1),
+                (y * cell_size + cell_size / 2).min(map.height - 1),
+            ));
+            index = came_from[index];
+        }
+        path.reverse();
+
+        Some(Guidance {
+            cell_size,
+            target_index: (LOOKAHEAD_CELLS - 1).min(path.len() - 1),
+            path,
+        })
+    }
+
+    pub fn get_target(&self) -> &Position {
+        &self.path[self.target_index]
+    }
+
+    /// moves the target further along the path, once the walker gets close to it
+    pub fn update_target(&mut self, pos: &Position) {
+        while self.target_index < self.path.len() - 1
+            && pos.distance(&self.path[self.target_index]) <= self.cell_size as f32
+        {
+            self.target_index += 1;
+        }
+    }
+
+    /// marks all cells along the path on the a_star debug layer
+    pub fn update_debug_layer(&self, debug_layers: &mut Option<DebugLayers>) {
+        let Some(debug_layers) = debug_layers else {
+            return;
+        };
+
+        let grid = debug_layers.bool_grid("a_star");
+        grid.fill(false);
+        for pos in self.path.iter() {
+            let x_start = (pos.x / self.cell_size) * self.cell_size;
+            let y_start = (pos.y / self.cell_size) * self.cell_size;
+            let x_end = (x_start + self.cell_size).min(grid.shape()[0]);
+            let y_end = (y_start + self.cell_size).min(grid.shape()[1]);
+            for x in x_start..x_end {
+                for y in y_start..y_end {
+                    grid[(x, y)] = true;
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use derivative::Derivative;
 use ndarray::Array2;
+use serde::{Deserialize, Serialize};
 
-#[derive(Derivative, Clone)]
+#[derive(Derivative, Clone, Serialize, Deserialize)]
 #[derivative(Debug)]
 pub struct Kernel {
     pub size: usize,
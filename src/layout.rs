@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{GenerationConfig, MapConfig, ThemeConfig},
+    position::Position,
+    random::{Random, Seed},
+};
+
+/// how often the map is enlarged if waypoints are too close, more are only needed due to rounding
+const MAX_ENLARGE_ITERATIONS: usize = 4;
+
+/// Shape family of a procedurally generated layout
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum LayoutShape {
+    /// bottom to top, alternating between left and right side
+    Climb,
+
+    /// rectangular spiral towards the center of the map
+    Spiral,
+
+    /// left to right, alternating between top and bottom side
+    Zigzag,
+
+    /// horizontal rows from top to bottom, alternating direction
+    Snake,
+}
+
+impl LayoutShape {
+    pub fn all() -> [LayoutShape; 4] {
+        [
+            LayoutShape::Climb,
+            LayoutShape::Spiral,
+            LayoutShape::Zigzag,
+            LayoutShape::Snake,
+        ]
+    }
+}
+
+/// Parameters for procedurally generating a `MapConfig`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutConfig {
+    pub shape: LayoutShape,
+
+    /// map width, scaled if a target length is set. Enlarged if waypoints would be too close.
+    pub width: usize,
+
+    /// map height, scaled if a target length is set. Enlarged if waypoints would be too close.
+    pub height: usize,
+
+    /// number of direction changes of the layout, so there are turns + 2 waypoints
+    pub turns: usize,
+
+    /// min euclidean distance between any two waypoints
+    pub min_waypoint_dist: f32,
+
+    /// if set, map dimensions are scaled so that the layout approximately has this length. Too
+    /// short lengths are exceeded, as the map is still enlarged if waypoints would be too close.
+    pub target_length: Option<f32>,
+
+    /// empty space between waypoints and map border, must leave enough room for the spawn
+    pub margin: usize,
+
+    /// max random displacement of waypoints relative to their spacing, in range [0, 0.5]
+    pub jitter: f32,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> LayoutConfig {
+        LayoutConfig {
+            shape: LayoutShape::Snake,
+            width: 300,
+            height: 200,
+            turns: 6,
+            min_waypoint_dist: 25.0,
+            target_length: None,
+            margin: 50,
+            jitter: 0.25,
+        }
+    }
+}
+
+impl LayoutConfig {
+    pub fn validate(&self, thm_config: &ThemeConfig) -> Result<(), &'static str> {
+        if self.width <= 2 * self.margin || self.height <= 2 * self.margin {
+            return Err("map is too small for layout margin");
+        }
+
+        let (left, right, top, bottom) = thm_config.get_spawn_room();
+        if self.margin < left.max(right).max(top).max(bottom) {
+            return Err("layout margin leaves no room for the spawn");
+        }
+
+        if !(0.0..=0.5).contains(&self.jitter) {
+            return Err("layout jitter must be in [0, 0.5]");
+        }
+
+        if self.target_length.is_some_and(|length| length <= 0.0) {
+            return Err("layout target length must be positive");
+        }
+
+        Ok(())
+    }
+
+    /// Generates a random map config. The same seed always results in the same layout.
+    pub fn generate(
+        &self,
+        seed: Seed,
+        thm_config: &ThemeConfig,
+    ) -> Result<MapConfig, &'static str> {
+        self.validate(thm_config)?;
+
+        // only the PRNG is used, so the generation config doesnt matter here
+        let mut rnd = Random::new(seed, &GenerationConfig::default());
+
+        let points = match self.shape {
+            LayoutShape::Climb => self.get_alternating_points(&mut rnd, true),
+            LayoutShape::Zigzag => self.get_alternating_points(&mut rnd, false),
+            LayoutShape::Snake => self.get_snake_points(&mut rnd),
+            LayoutShape::Spiral => self.get_spiral_points(&mut rnd),
+        };
+
+        // enlarge the map uniformly until no two waypoints are too close to each other
+        let (mut width, mut height) = self.get_scaled_size(&points);
+        for _ in 0..MAX_ENLARGE_ITERATIONS {
+            let waypoints: Vec<Position> = points
+                .iter()
+                .map(|&point| self.to_position(point, width, height))
+                .collect();
+
+            let min_dist = get_min_distance(&waypoints);
+            if min_dist >= self.min_waypoint_dist {
+                return Ok(MapConfig {
+                    name: format!("random_{:?}", self.shape).to_lowercase(),
+                    waypoints,
+                    width,
+                    height,
+                    waypoint_overrides: BTreeMap::new(),
+                    auto_fit: false,
+                });
+            }
+            if min_dist == 0.0 {
+                break;
+            }
+
+            let scale = self.min_waypoint_dist / min_dist;
+            width = ((width - 2 * self.margin) as f32 * scale).ceil() as usize + 2 * self.margin;
+            height = ((height - 2 * self.margin) as f32 * scale).ceil() as usize + 2 * self.margin;
+        }
+
+        Err("layout waypoints too close, reduce turns")
+    }
+
+    /// map dimensions, scaled uniformly so that the target length is approximately reached
+    fn get_scaled_size(&self, points: &[(f32, f32)]) -> (usize, usize) {
+        let Some(target_length) = self.target_length else {
+            return (self.width, self.height);
+        };
+
+        let inner_width = (self.width - 2 * self.margin) as f32;
+        let inner_height = (self.height - 2 * self.margin) as f32;
+        let length: f32 = points
+            .windows(2)
+            .map(|w| {
+                let dx = (w[1].0 - w[0].0) * inner_width;
+                let dy = (w[1].1 - w[0].1) * inner_height;
+                (dx * dx + dy * dy).sqrt()
+            })
+            .sum();
+
+        if length == 0.0 {
+            return (self.width, self.height);
+        }
+
+        let scale = target_length / length;
+        let width = (inner_width * scale).round() as usize + 2 * self.margin;
+        let height = (inner_height * scale).round() as usize + 2 * self.margin;
+        (
+            width.max(2 * self.margin + 1),
+            height.max(2 * self.margin + 1),
+        )
+    }
+
+    /// converts a point in unit coordinates to a map position inside the margin
+    fn to_position(&self, point: (f32, f32), width: usize, height: usize) -> Position {
+        let inner_width = (width - 2 * self.margin) as f32;
+        let inner_height = (height - 2 * self.margin) as f32;
+        Position::new(
+            self.margin + (point.0.clamp(0.0, 1.0) * inner_width).round() as usize,
+            self.margin + (point.1.clamp(0.0, 1.0) * inner_height).round() as usize,
+        )
+    }
+
+    /// random offset in range [-jitter * spacing, jitter * spacing]
+    fn get_jitter(&self, rnd: &mut Random, spacing: f32) -> f32 {
+        let max_offset = self.jitter * spacing;
+        rnd.get_f32_in_range(-max_offset, max_offset)
+    }
+
+    /// Waypoints alternate between two sides while progressing along the other axis. If vertical,
+    /// progress is from bottom to top, otherwise from left to right.
+    fn get_alternating_points(&self, rnd: &mut Random, vertical: bool) -> Vec<(f32, f32)> {
+        let point_count = self.turns + 2;
+        let spacing = 1.0 / (point_count - 1) as f32;
+        let side_width = 0.3;
+
+        (0..point_count)
+            .map(|index| {
+                let progress = (index as f32 * spacing
+                    + if index == 0 || index == point_count - 1 {
+                        0.0
+                    } else {
+                        self.get_jitter(rnd, spacing)
+                    })
+                .clamp(0.0, 1.0);
+
+                let side = if index % 2 == 0 {
+                    0.0
+                } else {
+                    1.0 - side_width
+                };
+                let offset = side + rnd.get_f32_in_range(0.0, side_width);
+
+                if vertical {
+                    (offset, 1.0 - progress)
+                } else {
+                    (progress, offset)
+                }
+            })
+            .collect()
+    }
+
+    /// Horizontal rows from top to bottom, starting at the left side. An odd number of turns
+    /// ends with a vertical segment, so the last row only consists of its first point.
+    fn get_snake_points(&self, rnd: &mut Random) -> Vec<(f32, f32)> {
+        let row_count = self.turns.div_ceil(2) + 1;
+        let row_spacing = 1.0 / (row_count.max(2) - 1) as f32;
+        let side_width = 0.15;
+
+        let mut points = Vec::new();
+        for row in 0..row_count {
+            let y = if row_count == 1 {
+                0.5
+            } else {
+                (row as f32 * row_spacing + self.get_jitter(rnd, row_spacing)).clamp(0.0, 1.0)
+            };
+            let left = rnd.get_f32_in_range(0.0, side_width);
+            let right = 1.0 - rnd.get_f32_in_range(0.0, side_width);
+
+            let (first, second) = if row % 2 == 0 {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            points.push((first, y));
+            if row + 1 < row_count || self.turns % 2 == 0 {
+                points.push((second, y));
+            }
+        }
+
+        points
+    }
+
+    /// rectangular spiral from the bottom left corner towards the center
+    fn get_spiral_points(&self, rnd: &mut Random) -> Vec<(f32, f32)> {
+        let rounds = (self.turns as f32 / 4.0).ceil() + 1.0;
+        let inset = 0.5 / rounds;
+
+        // bounds of the remaining spiral area: left, right, top, bottom
+        let (mut left, mut right, mut top, mut bottom) = (inset, 1.0, 0.0, 1.0);
+        let mut pos = (0.0, 1.0);
+        let mut points = vec![pos];
+
+        for turn in 0..=self.turns {
+            let jitter = self.get_jitter(rnd, inset);
+            match turn % 4 {
+                0 => {
+                    pos.0 = right + jitter.min(0.0);
+                    bottom -= inset;
+                }
+                1 => {
+                    pos.1 = top - jitter.min(0.0);
+                    right -= inset;
+                }
+                2 => {
+                    pos.0 = left - jitter.min(0.0);
+                    top += inset;
+                }
+                _ => {
+                    pos.1 = bottom + jitter.min(0.0);
+                    left += inset;
+                }
+            }
+            points.push(pos);
+        }
+
+        points
+    }
+}
+
+/// smallest euclidean distance between any two positions
+fn get_min_distance(positions: &[Position]) -> f32 {
+    let mut min_dist = f32::INFINITY;
+    for (index, pos) in positions.iter().enumerate() {
+        for other in positions[index + 1..].iter() {
+            min_dist = min_dist.min(pos.distance(other));
+        }
+    }
+
+    min_dist
+}
@@ -1,18 +1,34 @@
 pub mod args;
+pub mod benchmark;
+pub mod builder;
 pub mod config;
 pub mod debug;
+pub mod difficulty;
 pub mod editor;
 pub mod fps_control;
 pub mod generator;
+pub mod golden;
 pub mod gui;
+pub mod guidance;
 pub mod kernel;
+pub mod layout;
 pub mod map;
 pub mod map_camera;
+pub mod metrics;
+pub mod movement;
 pub mod noise;
+pub mod patterns;
+pub mod pipeline;
 pub mod position;
 pub mod post_processing;
+pub mod progress;
 pub mod random;
 pub mod rendering;
+pub mod report;
+pub mod retry;
+pub mod snapshot;
+pub mod transform;
+pub mod tuning;
 pub mod twmap_export;
 pub mod utils;
 pub mod walker;
@@ -1,9 +1,11 @@
 use crate::{
     kernel::Kernel,
     position::{Position, ShiftDirection},
+    transform::MapTransform,
     twmap_export::TwExport,
 };
-use ndarray::{s, Array2, Axis};
+use ndarray::{s, Array2};
+use serde::{Deserialize, Serialize};
 
 use std::path::PathBuf;
 
@@ -16,7 +18,7 @@ pub enum BlockTypeTW {
     Empty,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BlockType {
     /// regular empty block, no extra conditions
     Empty,
@@ -76,6 +78,17 @@ impl BlockType {
     pub fn is_empty(&self) -> bool {
         matches!(self, BlockType::Empty)
     }
+
+    /// any kind of empty block, including the ones reserved for fade, rooms and platforms
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            BlockType::Empty
+                | BlockType::EmptyFade
+                | BlockType::EmptyRoom
+                | BlockType::EmptyPlatform
+        )
+    }
 }
 
 pub enum Overwrite {
@@ -126,7 +139,7 @@ pub enum KernelType {
     Inner,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Map {
     pub grid: Array2<BlockType>,
     pub font_layer: Array2<Option<char>>,
@@ -155,6 +168,21 @@ impl Map {
         }
     }
 
+    /// Stable hash of the exported map size and blocks, used to compare generated maps across
+    /// runs and machines.
+    pub fn grid_hash(&self) -> u64 {
+        let mut bytes = Vec::with_capacity(self.grid.len() + 16);
+        bytes.extend_from_slice(&(self.width as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u64).to_le_bytes());
+        bytes.extend(
+            self.grid
+                .iter()
+                .map(|block_type| block_type.to_tw_game_id()),
+        );
+
+        seahash::hash(&bytes)
+    }
+
     pub fn apply_kernel(
         &mut self,
         pos: &Position,
@@ -392,16 +420,87 @@ impl Map {
         }
     }
 
-    /// Flip grid and font layer of map on the x axis.
-    /// TODO: Chunked rendering and debug layers are currently broken
-    pub fn flip_x_axis(&mut self) {
-        // flip map grid
-        self.grid.invert_axis(Axis(0));
+    /// Crops unused solid border of the map, so that only `margin` blocks remain around the used
+    /// area (any non hookable block or text). Returns the top left position of the cropped area
+    /// in the original map, or None if the entire map is unused. See Generator::crop for also
+    /// cropping the walker and debug layers.
+    pub fn crop_to_margin(&mut self, margin: usize) -> Option<Position> {
+        let mut top_left = Position::new(usize::MAX, usize::MAX);
+        let mut bot_right = Position::new(0, 0);
+        for ((x, y), block_type) in self.grid.indexed_iter() {
+            if *block_type != BlockType::Hookable || self.font_layer[(x, y)].is_some() {
+                top_left = Position::new(top_left.x.min(x), top_left.y.min(y));
+                bot_right = Position::new(bot_right.x.max(x), bot_right.y.max(y));
+            }
+        }
+
+        if top_left.x > bot_right.x {
+            return None; // nothing used
+        }
+
+        let top_left = Position::new(
+            top_left.x.saturating_sub(margin),
+            top_left.y.saturating_sub(margin),
+        );
+        let bot_right = Position::new(
+            (bot_right.x + margin).min(self.width - 1),
+            (bot_right.y + margin).min(self.height - 1),
+        );
+        let area = s![top_left.x..=bot_right.x, top_left.y..=bot_right.y];
+
+        self.grid = self.grid.slice(area).to_owned();
+        self.font_layer = self.font_layer.slice(area).to_owned();
+        self.noise_overlay = self
+            .noise_overlay
+            .as_ref()
+            .map(|n| n.slice(area).to_owned());
+        self.noise_background = self
+            .noise_background
+            .as_ref()
+            .map(|n| n.slice(area).to_owned());
+        self.width = bot_right.x - top_left.x + 1;
+        self.height = bot_right.y - top_left.y + 1;
+
+        // everything has to be redrawn anyways
+        self.chunk_edited = Array2::from_elem(
+            (
+                self.width.div_ceil(self.chunk_size),
+                self.height.div_ceil(self.chunk_size),
+            ),
+            true,
+        );
+
+        Some(top_left)
+    }
+
+    /// Transforms grid, font and noise layers. As all blocks move, all chunks are marked as
+    /// edited. Text remains readable for flips, but is vertical after rotating.
+    pub fn transform(&mut self, transform: &MapTransform) {
+        transform.apply_array(&mut self.grid);
+        transform.apply_array(&mut self.font_layer);
+        if let Some(noise_overlay) = self.noise_overlay.as_mut() {
+            transform.apply_array(noise_overlay);
+        }
+        if let Some(noise_background) = self.noise_background.as_mut() {
+            transform.apply_array(noise_background);
+        }
+
+        (self.width, self.height) = transform.apply_size(self.width, self.height);
+        self.chunk_edited = Array2::from_elem(
+            (
+                self.width.div_ceil(self.chunk_size),
+                self.height.div_ceil(self.chunk_size),
+            ),
+            true,
+        );
 
-        // flip font layers
-        self.font_layer.invert_axis(Axis(0));
+        if *transform == MapTransform::FlipX {
+            self.fix_flipped_font_layer();
+        }
+    }
 
-        // then fix character ordering of flipped font layer
+    /// reverses the character order of all character groups, after flipping on the x axis
+    fn fix_flipped_font_layer(&mut self) {
         for y in 0..self.height {
             let mut x = 0;
             while x < self.width {
@@ -0,0 +1,282 @@
+use std::fmt;
+
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    map::{BlockType, Map},
+    position::{Position, ShiftDirection},
+    post_processing::{distance_transform, flood_fill, get_platform_gaps},
+    report::GenerationReport,
+};
+
+/// amount of main path blocks between two samples for detecting direction changes, so that
+/// diagonal staircases of the path don't count as turns
+const TURN_SAMPLE_DISTANCE: usize = 5;
+
+/// Metrics of a finished map, derived from the map itself and the generation report
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MapMetrics {
+    /// flood fill distance from spawn to finish
+    pub main_path_length: usize,
+
+    /// estimated corridor width including freeze at each main path block, index is the width
+    /// in blocks
+    pub corridor_width_histogram: Vec<usize>,
+
+    pub mean_corridor_width: f32,
+
+    /// freeze blocks per open block in the direct neighborhood of the main path, see
+    /// BlockType::is_open
+    pub freeze_ratio: f32,
+
+    /// direction changes along the main path
+    pub direction_changes: usize,
+
+    /// upwards steps along the main path
+    pub climb_height: usize,
+
+    /// length of the map config waypoints, see MapConfig::get_map_length
+    pub map_length: f32,
+
+    pub platform_count: usize,
+
+    /// smallest and largest flood fill distance between two consecutive platforms
+    pub platform_gap_bounds: Option<(usize, usize)>,
+
+    pub mean_platform_gap: Option<f32>,
+
+    /// generated skips, including freeze skips
+    pub skip_count: usize,
+
+    pub freeze_skip_count: usize,
+
+    /// amount of open blocks in the entire map, see BlockType::is_open
+    pub empty_blocks: usize,
+
+    /// largest distance of any open block to the next block that is not open
+    pub max_open_distance: f32,
+}
+
+impl MapMetrics {
+    /// Analyzes a finished map. The main path is determined by a new flood fill, so the
+    /// report only has to provide spawn, finish, platforms and skips of the same map.
+    pub fn new(map: &Map, report: &GenerationReport) -> Result<MapMetrics, &'static str> {
+        let ff = flood_fill(
+            map,
+            std::slice::from_ref(&report.spawn),
+            Some(&report.finish),
+            false,
+        )?;
+        if ff.distance[report.finish.as_index()].is_none() {
+            return Err("no valid path to finish");
+        }
+        // flood fill path starts at the finish
+        let mut main_path = ff.path.ok_or("flood fill has no main path")?;
+        main_path.reverse();
+
+        // freeze is part of a corridor, so only solid blocks are walls
+        let wall_distance = distance_transform(map, BlockType::is_solid);
+        let open_distance = distance_transform(map, |block_type| !block_type.is_open());
+
+        let mut corridor_width_histogram = Vec::new();
+        for pos in main_path.iter() {
+            let width = get_corridor_width(wall_distance[pos.as_index()]);
+            if corridor_width_histogram.len() <= width {
+                corridor_width_histogram.resize(width + 1, 0);
+            }
+            corridor_width_histogram[width] += 1;
+        }
+        let mean_corridor_width = corridor_width_histogram
+            .iter()
+            .enumerate()
+            .map(|(width, count)| width * count)
+            .sum::<usize>() as f32
+            / main_path.len() as f32;
+
+        let platform_gaps =
+            get_platform_gaps(report.platforms.iter().map(|plat| plat.flood_fill_dist));
+
+        Ok(MapMetrics {
+            main_path_length: main_path.len() - 1,
+            corridor_width_histogram,
+            mean_corridor_width,
+            freeze_ratio: get_freeze_ratio(map, &main_path),
+            direction_changes: count_direction_changes(&main_path),
+            climb_height: main_path.windows(2).filter(|p| p[1].y < p[0].y).count(),
+            map_length: report.map_length,
+            platform_count: report.platforms.len(),
+            platform_gap_bounds: platform_gaps
+                .iter()
+                .min()
+                .zip(platform_gaps.iter().max())
+                .map(|(min, max)| (*min, *max)),
+            mean_platform_gap: (!platform_gaps.is_empty())
+                .then(|| platform_gaps.iter().sum::<usize>() as f32 / platform_gaps.len() as f32),
+            skip_count: report.skips.len(),
+            freeze_skip_count: report.skips.iter().filter(|s| s.freeze_only).count(),
+            empty_blocks: map.grid.iter().filter(|b| b.is_open()).count(),
+            max_open_distance: open_distance.iter().cloned().fold(0.0, f32::max),
+        })
+    }
+}
+
+/// A block in the middle of a corridor with width w has a distance of (w + 1) / 2 to the walls.
+/// Blocks closer to a wall result in a smaller width.
+fn get_corridor_width(distance: f32) -> usize {
+    ((2.0 * distance - 1.0).round() as usize).max(1)
+}
+
+fn get_freeze_ratio(map: &Map, main_path: &[Position]) -> f32 {
+    let mut visited = Array2::from_elem((map.width, map.height), false);
+    let mut freeze_blocks = 0;
+    let mut open_blocks = 0;
+
+    for pos in main_path.iter() {
+        for x in pos.x.saturating_sub(1)..=(pos.x + 1).min(map.width - 1) {
+            for y in pos.y.saturating_sub(1)..=(pos.y + 1).min(map.height - 1) {
+                if visited[[x, y]] {
+                    continue;
+                }
+                visited[[x, y]] = true;
+
+                let block_type = &map.grid[[x, y]];
+                if block_type.is_freeze() {
+                    freeze_blocks += 1;
+                } else if block_type.is_open() {
+                    open_blocks += 1;
+                }
+            }
+        }
+    }
+
+    freeze_blocks as f32 / open_blocks.max(1) as f32
+}
+
+fn count_direction_changes(main_path: &[Position]) -> usize {
+    let samples: Vec<&Position> = main_path.iter().step_by(TURN_SAMPLE_DISTANCE).collect();
+    let directions: Vec<ShiftDirection> = samples
+        .windows(2)
+        .map(|pos| pos[0].get_greedy_shift(pos[1]))
+        .collect();
+
+    directions.windows(2).filter(|dir| dir[0] != dir[1]).count()
+}
+
+impl fmt::Display for MapMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "main path length:   {}", self.main_path_length)?;
+        writeln!(f, "corridor width:     {:.2}", self.mean_corridor_width)?;
+        for (width, count) in self.corridor_width_histogram.iter().enumerate() {
+            if *count > 0 {
+                writeln!(f, "  {:>3} blocks:       {}", width, count)?;
+            }
+        }
+        writeln!(f, "freeze ratio:       {:.2}", self.freeze_ratio)?;
+        writeln!(f, "direction changes:  {}", self.direction_changes)?;
+        writeln!(f, "climb height:       {}", self.climb_height)?;
+        writeln!(f, "platforms:          {}", self.platform_count)?;
+        if let (Some((min, max)), Some(mean)) = (self.platform_gap_bounds, self.mean_platform_gap) {
+            writeln!(
+                f,
+                "platform gaps:      {} - {} (mean {:.1})",
+                min, max, mean
+            )?;
+        }
+        writeln!(
+            f,
+            "skips:              {} ({} freeze)",
+            self.skip_count, self.freeze_skip_count
+        )?;
+        writeln!(f, "empty blocks:       {}", self.empty_blocks)?;
+        write!(f, "max open distance:  {:.2}", self.max_open_distance)
+    }
+}
+
+/// Thresholds a successfully generated map has to fulfill, otherwise it is rejected. Unset
+/// thresholds are not checked.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AcceptanceCriteria {
+    pub min_path_length: Option<usize>,
+    pub max_path_length: Option<usize>,
+
+    /// largest allowed distance of any open block to the next block that is not open
+    pub max_open_distance: Option<f32>,
+
+    /// bounds of the gaps between consecutive platforms, ignored for less than two platforms
+    pub min_platform_gap: Option<usize>,
+    pub max_platform_gap: Option<usize>,
+
+    pub max_skip_count: Option<usize>,
+}
+
+impl AcceptanceCriteria {
+    /// whether no threshold is set, so every map is accepted
+    pub fn is_empty(&self) -> bool {
+        *self == AcceptanceCriteria::default()
+    }
+
+    /// returns the reason if the metrics violate a threshold
+    pub fn check(&self, metrics: &MapMetrics) -> Result<(), &'static str> {
+        if self
+            .min_path_length
+            .is_some_and(|min| metrics.main_path_length < min)
+        {
+            return Err("main path too short");
+        }
+        if self
+            .max_path_length
+            .is_some_and(|max| metrics.main_path_length > max)
+        {
+            return Err("main path too long");
+        }
+        if self
+            .max_open_distance
+            .is_some_and(|max| metrics.max_open_distance > max)
+        {
+            return Err("open area too large");
+        }
+        if let Some((min_gap, max_gap)) = metrics.platform_gap_bounds {
+            if self.min_platform_gap.is_some_and(|min| min_gap < min) {
+                return Err("platform gap too small");
+            }
+            if self.max_platform_gap.is_some_and(|max| max_gap > max) {
+                return Err("platform gap too large");
+            }
+        }
+        if self
+            .max_skip_count
+            .is_some_and(|max| metrics.skip_count > max)
+        {
+            return Err("too many skips");
+        }
+
+        Ok(())
+    }
+
+    /// Determines the metrics of a finished map only if any threshold is set. Maps whose
+    /// metrics can't be determined are rejected as well.
+    pub fn check_map(&self, map: &Map, report: &GenerationReport) -> Result<(), &'static str> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        self.check(&MapMetrics::new(map, report)?)
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let (Some(min), Some(max)) = (self.min_path_length, self.max_path_length) {
+            if min > max {
+                return Err("min path length must be <= max path length");
+            }
+        }
+        if let (Some(min), Some(max)) = (self.min_platform_gap, self.max_platform_gap) {
+            if min > max {
+                return Err("min platform gap must be <= max platform gap");
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::VecDeque;
+
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+use crate::{map::Map, position::Position};
+
+/// Path blocks count as reached if a reachable block is within this chebyshev distance, as
+/// the movement model only covers a subset of all possible tee positions.
+const REACH_TOLERANCE: usize = 2;
+
+/// hook directions, as integer vectors
+const HOOK_DIRECTIONS: [(i32, i32); 16] = [
+    (1, 0),
+    (2, -1),
+    (1, -1),
+    (1, -2),
+    (0, -1),
+    (-1, -2),
+    (-1, -1),
+    (-2, -1),
+    (-1, 0),
+    (-2, 1),
+    (-1, 1),
+    (-1, 2),
+    (0, 1),
+    (1, 2),
+    (1, 1),
+    (2, 1),
+];
+
+/// Simplified DDNet movement in block units. A tee occupies a single block, solid blocks block
+/// movement and entering freeze is considered dead. In air, a tee can move one block
+/// sideways per block it rises or falls, or freely sideways while hanging on a hook.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MovementModel {
+    /// blocks a tee rises with a ground jump
+    pub jump_height: usize,
+
+    /// blocks a tee rises with its air jump, which is restored on the ground
+    pub air_jump_height: usize,
+
+    /// maximum hook length in blocks, only hookable blocks can be hooked
+    pub hook_range: usize,
+}
+
+impl Default for MovementModel {
+    /// roughly matches DDNet: jumps rise ~5.4 and ~4.5 blocks, hook length is 380 units
+    fn default() -> MovementModel {
+        MovementModel {
+            jump_height: 5,
+            air_jump_height: 4,
+            hook_range: 12,
+        }
+    }
+}
+
+/// tee state during the search
+#[derive(Debug, Clone, Copy)]
+struct TeeState {
+    x: usize,
+    y: usize,
+
+    /// blocks the tee still rises from its current jump
+    rise: usize,
+
+    air_jump: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReachabilityResult {
+    /// blocks that a tee can reach from spawn
+    pub reachable: Array2<bool>,
+
+    pub finish_reachable: bool,
+
+    /// first and last block of each main path section that could not be reached
+    pub unreachable_sections: Vec<(Position, Position)>,
+}
+
+impl MovementModel {
+    fn is_free(map: &Map, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < map.width
+            && (y as usize) < map.height
+            && !map.grid[[x as usize, y as usize]].is_solid()
+            && !map.grid[[x as usize, y as usize]].is_freeze()
+    }
+
+    fn get_state_index(&self, map: &Map, state: &TeeState) -> usize {
+        let max_rise = self.jump_height.max(self.air_jump_height);
+        ((state.x * map.height + state.y) * (max_rise + 1) + state.rise) * 2
+            + state.air_jump as usize
+    }
+
+    /// all states that directly follow a state
+    fn get_next_states(
+        &self,
+        map: &Map,
+        hanging: &Array2<bool>,
+        state: &TeeState,
+        next: &mut Vec<TeeState>,
+    ) {
+        let (x, y) = (state.x as i32, state.y as i32);
+        let grounded = y + 1 < map.height as i32 && map.grid[[state.x, state.y + 1]].is_solid();
+        let air_jump = state.air_jump || grounded;
+
+        // rising after a jump, can be stopped at any time
+        if state.rise > 0 {
+            for dx in -1..=1 {
+                if MovementModel::is_free(map, x + dx, y - 1) {
+                    next.push(TeeState {
+                        x: (x + dx) as usize,
+                        y: state.y - 1,
+                        rise: state.rise - 1,
+                        air_jump,
+                    });
+                }
+            }
+            next.push(TeeState {
+                rise: 0,
+                air_jump,
+                ..*state
+            });
+        } else if grounded {
+            for dx in [-1, 1] {
+                if MovementModel::is_free(map, x + dx, y) {
+                    next.push(TeeState {
+                        x: (x + dx) as usize,
+                        y: state.y,
+                        rise: 0,
+                        air_jump: true,
+                    });
+                }
+            }
+            next.push(TeeState {
+                rise: self.jump_height,
+                air_jump: true,
+                ..*state
+            });
+        } else {
+            for dx in -1..=1 {
+                if MovementModel::is_free(map, x + dx, y + 1) {
+                    next.push(TeeState {
+                        x: (x + dx) as usize,
+                        y: state.y + 1,
+                        rise: 0,
+                        air_jump,
+                    });
+                }
+            }
+            if air_jump {
+                next.push(TeeState {
+                    rise: self.air_jump_height,
+                    air_jump: false,
+                    ..*state
+                });
+            }
+        }
+
+        // hanging on a hook allows moving sideways in air without falling
+        if hanging[[state.x, state.y]] {
+            for dx in [-1, 1] {
+                if MovementModel::is_free(map, x + dx, y) {
+                    next.push(TeeState {
+                        x: (x + dx) as usize,
+                        y: state.y,
+                        rise: 0,
+                        air_jump,
+                    });
+                }
+            }
+        }
+
+        // hook pulls the tee towards the hooked block, it can be released at any time
+        let mut path = Vec::new();
+        for direction in HOOK_DIRECTIONS {
+            let Some((_, hook_y)) = self.cast_hook(map, state, direction, &mut path) else {
+                continue;
+            };
+
+            for (path_x, path_y) in path.iter() {
+                if !MovementModel::is_free(map, *path_x, *path_y) {
+                    break;
+                }
+                next.push(TeeState {
+                    x: *path_x as usize,
+                    y: *path_y as usize,
+                    rise: 0,
+                    air_jump,
+                });
+            }
+
+            // for steep hooks, air control can cancel the sideways pull
+            let (dir_x, dir_y) = direction;
+            if dir_y.abs() > dir_x.abs() {
+                for pull_y in (1..(hook_y - y).abs()).map(|dist| y + dist * dir_y.signum()) {
+                    if !MovementModel::is_free(map, x, pull_y) {
+                        break;
+                    }
+                    next.push(TeeState {
+                        x: state.x,
+                        y: pull_y as usize,
+                        rise: 0,
+                        air_jump,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns the block a hook in the given direction attaches to, if any. The blocks between
+    /// the tee and the hooked block are stored in path.
+    fn cast_hook(
+        &self,
+        map: &Map,
+        state: &TeeState,
+        (dir_x, dir_y): (i32, i32),
+        path: &mut Vec<(i32, i32)>,
+    ) -> Option<(i32, i32)> {
+        let (x, y) = (state.x as i32, state.y as i32);
+        let steps = dir_x.abs().max(dir_y.abs());
+        let step_length = ((dir_x * dir_x + dir_y * dir_y) as f32).sqrt() / steps as f32;
+        let max_steps = (self.hook_range as f32 / step_length) as i32;
+
+        path.clear();
+        for step in 1..=max_steps {
+            let hook_x = x + (step * dir_x + steps / 2 * dir_x.signum()) / steps;
+            let hook_y = y + (step * dir_y + steps / 2 * dir_y.signum()) / steps;
+            if hook_x < 0
+                || hook_y < 0
+                || hook_x as usize >= map.width
+                || hook_y as usize >= map.height
+            {
+                return None;
+            }
+
+            // hook passes through freeze
+            if map.grid[[hook_x as usize, hook_y as usize]].is_solid() {
+                return Some((hook_x, hook_y));
+            }
+            path.push((hook_x, hook_y));
+        }
+
+        None
+    }
+
+    /// blocks where a tee can hook a block above it
+    fn get_hanging_blocks(&self, map: &Map) -> Array2<bool> {
+        let mut path = Vec::new();
+
+        Array2::from_shape_fn((map.width, map.height), |(x, y)| {
+            let state = TeeState {
+                x,
+                y,
+                rise: 0,
+                air_jump: false,
+            };
+
+            MovementModel::is_free(map, x as i32, y as i32)
+                && HOOK_DIRECTIONS
+                    .iter()
+                    .filter(|(_, dir_y)| *dir_y < 0)
+                    .any(|direction| self.cast_hook(map, &state, *direction, &mut path).is_some())
+        })
+    }
+
+    /// Searches all blocks a tee can reach from spawn. If a main path from spawn to finish is
+    /// given, sections of it that are not reached are reported in the same order.
+    pub fn get_reachability(
+        &self,
+        map: &Map,
+        spawn: &Position,
+        finish: &Position,
+        main_path: Option<&[Position]>,
+    ) -> ReachabilityResult {
+        let max_rise = self.jump_height.max(self.air_jump_height);
+        // one flag per tee state, with the default model that is 12 bytes per block, so ~12MB
+        // for a 1000x1000 map
+        let mut visited = vec![false; map.width * map.height * (max_rise + 1) * 2];
+        let mut reachable = Array2::from_elem((map.width, map.height), false);
+        let mut queue = VecDeque::new();
+        let mut next = Vec::new();
+        let hanging = self.get_hanging_blocks(map);
+
+        let start = TeeState {
+            x: spawn.x,
+            y: spawn.y,
+            rise: 0,
+            air_jump: true,
+        };
+        visited[self.get_state_index(map, &start)] = true;
+        queue.push_back(start);
+
+        while let Some(state) = queue.pop_front() {
+            reachable[[state.x, state.y]] = true;
+
+            next.clear();
+            self.get_next_states(map, &hanging, &state, &mut next);
+            for next_state in next.iter() {
+                let index = self.get_state_index(map, next_state);
+                if !visited[index] {
+                    visited[index] = true;
+                    queue.push_back(*next_state);
+                }
+            }
+        }
+
+        let is_reached = |pos: &Position| {
+            let x_range = pos.x.saturating_sub(REACH_TOLERANCE)
+                ..=(pos.x + REACH_TOLERANCE).min(map.width - 1);
+            x_range.into_iter().any(|x| {
+                (pos.y.saturating_sub(REACH_TOLERANCE)
+                    ..=(pos.y + REACH_TOLERANCE).min(map.height - 1))
+                    .any(|y| reachable[[x, y]])
+            })
+        };
+
+        let mut unreachable_sections = Vec::new();
+        let mut section_start: Option<&Position> = None;
+        let main_path = main_path.unwrap_or(&[]);
+        for (index, pos) in main_path.iter().enumerate() {
+            if is_reached(pos) {
+                if let Some(start) = section_start.take() {
+                    unreachable_sections.push((start.clone(), main_path[index - 1].clone()));
+                }
+            } else if section_start.is_none() {
+                section_start = Some(pos);
+            }
+        }
+        if let (Some(start), Some(end)) = (section_start, main_path.last()) {
+            unreachable_sections.push((start.clone(), end.clone()));
+        }
+
+        ReachabilityResult {
+            finish_reachable: is_reached(finish),
+            reachable,
+            unreachable_sections,
+        }
+    }
+}
@@ -5,9 +5,11 @@ use noise::{
     Fbm, Perlin, Worley,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::map::Map;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub enum Noise {
     Perlin,
     Worley,
@@ -0,0 +1,266 @@
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    map::{BlockType, Map, Overwrite},
+    position::Position,
+    random::{Random, RandomDist, RandomDistConfig},
+};
+
+/// Condition a single block of a pattern has to fulfill. In pattern rows, each predicate is
+/// written as a single character, see from_char.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockPredicate {
+    /// '.', any block
+    Any,
+
+    /// '_', regular empty block
+    Empty,
+
+    /// 'X', hookable or platform block
+    Solid,
+
+    /// 'F', freeze block
+    Freeze,
+
+    /// 'O', any block that is not solid
+    NonSolid,
+}
+
+impl BlockPredicate {
+    pub fn from_char(c: char) -> Result<BlockPredicate, &'static str> {
+        match c {
+            '.' => Ok(BlockPredicate::Any),
+            '_' => Ok(BlockPredicate::Empty),
+            'X' => Ok(BlockPredicate::Solid),
+            'F' => Ok(BlockPredicate::Freeze),
+            'O' => Ok(BlockPredicate::NonSolid),
+            _ => Err("invalid pattern character"),
+        }
+    }
+
+    pub fn matches(&self, block_type: &BlockType) -> bool {
+        match self {
+            BlockPredicate::Any => true,
+            BlockPredicate::Empty => block_type.is_empty(),
+            BlockPredicate::Solid => block_type.is_solid(),
+            BlockPredicate::Freeze => block_type.is_freeze(),
+            BlockPredicate::NonSolid => !block_type.is_solid(),
+        }
+    }
+}
+
+/// Kernel of block predicates, indexed by [x, y] like the map grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub kernel: Array2<BlockPredicate>,
+}
+
+impl Pattern {
+    /// Parses a pattern from rows of predicate characters, whitespace is ignored. Both
+    /// dimensions have to be odd, so that the pattern has a center block.
+    pub fn parse(rows: &[String]) -> Result<Pattern, &'static str> {
+        let rows: Vec<Vec<BlockPredicate>> = rows
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(BlockPredicate::from_char)
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        if width == 0 || rows.iter().any(|row| row.len() != width) {
+            return Err("pattern rows must be non empty and of equal length");
+        }
+        if width % 2 == 0 || height % 2 == 0 {
+            return Err("pattern dimensions must be odd");
+        }
+
+        Ok(Pattern {
+            kernel: Array2::from_shape_fn((width, height), |(x, y)| rows[y][x]),
+        })
+    }
+
+    /// rotated by 90 degrees clockwise
+    pub fn rotated(&self) -> Pattern {
+        let (width, height) = self.kernel.dim();
+        Pattern {
+            kernel: Array2::from_shape_fn((height, width), |(x, y)| {
+                self.kernel[[y, height - 1 - x]]
+            }),
+        }
+    }
+
+    /// mirrored along the vertical axis
+    pub fn mirrored(&self) -> Pattern {
+        let (width, height) = self.kernel.dim();
+        Pattern {
+            kernel: Array2::from_shape_fn((width, height), |(x, y)| {
+                self.kernel[[width - 1 - x, y]]
+            }),
+        }
+    }
+
+    /// all distinct rotations and mirrors of the pattern, starting with the pattern itself
+    pub fn variants(&self) -> Vec<Pattern> {
+        let mut variants: Vec<Pattern> = Vec::new();
+
+        for mut variant in [self.clone(), self.mirrored()] {
+            for _ in 0..4 {
+                if !variants.contains(&variant) {
+                    variants.push(variant.clone());
+                }
+                variant = variant.rotated();
+            }
+        }
+
+        variants
+    }
+
+    pub fn matches_at(&self, map: &Map, top_left: &Position) -> bool {
+        let (width, height) = self.kernel.dim();
+        if top_left.x + width > map.width || top_left.y + height > map.height {
+            return false;
+        }
+
+        self.kernel.indexed_iter().all(|((x, y), predicate)| {
+            predicate.matches(&map.grid[[top_left.x + x, top_left.y + y]])
+        })
+    }
+
+    /// top left positions of all matches in the map
+    pub fn find_matches(&self, map: &Map) -> Vec<Position> {
+        let (width, height) = self.kernel.dim();
+        let mut matches = Vec::new();
+
+        for x in 0..map.width.saturating_sub(width - 1) {
+            for y in 0..map.height.saturating_sub(height - 1) {
+                let top_left = Position::new(x, y);
+                if self.matches_at(map, &top_left) {
+                    matches.push(top_left);
+                }
+            }
+        }
+
+        matches
+    }
+}
+
+/// how a matched forbidden pattern is broken up
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternReplacement {
+    /// leave the pattern as it is
+    Keep,
+
+    /// replace the center block with an empty block, if it is hookable or freeze
+    EmptyCenter,
+
+    /// replace the center block with a hookable block, if it is empty
+    SolidCenter,
+
+    /// remove all hookable blocks of the matched area
+    RemoveHookable,
+}
+
+impl PatternReplacement {
+    pub fn apply(&self, map: &mut Map, top_left: &Position, bot_right: &Position) {
+        let center = Position::new(
+            (top_left.x + bot_right.x) / 2,
+            (top_left.y + bot_right.y) / 2,
+        );
+
+        match self {
+            PatternReplacement::Keep => {}
+            PatternReplacement::EmptyCenter => map.set_area(
+                &center,
+                &center,
+                &BlockType::Empty,
+                &Overwrite::ReplaceHookableFreeze,
+            ),
+            PatternReplacement::SolidCenter => map.set_area(
+                &center,
+                &center,
+                &BlockType::Hookable,
+                &Overwrite::ReplaceEmptyOnly,
+            ),
+            PatternReplacement::RemoveHookable => map.set_area(
+                top_left,
+                bot_right,
+                &BlockType::Empty,
+                &Overwrite::ReplaceHookableOnly,
+            ),
+        }
+    }
+}
+
+/// Pattern that should not appear in generated maps, see forbidden_patterns of the generation
+/// config. All rotations and mirrors of the pattern are detected as well.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ForbiddenPattern {
+    pub name: String,
+
+    /// rows of predicate characters, see BlockPredicate
+    pub rows: Vec<String>,
+
+    /// replacement strategies and their probabilities
+    pub replacements: RandomDistConfig<PatternReplacement>,
+}
+
+impl ForbiddenPattern {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        Pattern::parse(&self.rows)?;
+
+        let value_count = self.replacements.values.as_ref().map_or(0, |v| v.len());
+        if value_count == 0 || value_count != self.replacements.probs.len() {
+            return Err("forbidden pattern requires a probability for each replacement");
+        }
+        if self.replacements.probs.iter().all(|prob| *prob <= 0.0) {
+            return Err("forbidden pattern replacement probabilities must not all be zero");
+        }
+
+        Ok(())
+    }
+}
+
+/// Detects all forbidden patterns and replaces each match using one of its replacement
+/// strategies. Matches are checked against the current map, so a match that was already
+/// broken up by a previous replacement is skipped. Returns all matched blocks.
+pub fn replace_forbidden_patterns(
+    map: &mut Map,
+    forbidden_patterns: &[ForbiddenPattern],
+    rnd: &mut Random,
+) -> Result<Array2<bool>, &'static str> {
+    let mut matched = Array2::from_elem((map.width, map.height), false);
+
+    for forbidden in forbidden_patterns.iter() {
+        let replacements = RandomDist::new(forbidden.replacements.clone());
+
+        for variant in Pattern::parse(&forbidden.rows)?.variants() {
+            let (width, height) = variant.kernel.dim();
+
+            for top_left in variant.find_matches(map) {
+                if !variant.matches_at(map, &top_left) {
+                    continue;
+                }
+                let bot_right = top_left.shifted_by(width as i32 - 1, height as i32 - 1)?;
+
+                let replacement = rnd.sample_dist(&replacements);
+                replacement.apply(map, &top_left, &bot_right);
+
+                matched
+                    .slice_mut(ndarray::s![
+                        top_left.x..=bot_right.x,
+                        top_left.y..=bot_right.y
+                    ])
+                    .fill(true);
+            }
+        }
+    }
+
+    Ok(matched)
+}
@@ -0,0 +1,508 @@
+use std::time::{Duration, Instant};
+
+use ndarray::Array2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{GenerationConfig, ThemeConfig},
+    debug::DebugLayers,
+    generator::Generator,
+    map::{BlockType, BlockTypeTW},
+    movement::{MovementModel, ReachabilityResult},
+    patterns,
+    position::Position,
+    post_processing::{
+        self as post, flood_fill, FloodFillResult, FloorPosition, PlatformCandidate, Skip,
+    },
+};
+
+/// Single post processing stage. Stages exchange their results using a `PostContext`, so a
+/// stage can only be used after the stages that provide its inputs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PostStage {
+    /// place the start room at the spawn position
+    SpawnRoom,
+
+    /// remove freeze blobs that are smaller than min_freeze_size
+    RemoveFreezeBlobs,
+
+    /// flood fill from spawn to the final walker position
+    FloodFill,
+
+    /// replace empty blocks next to hookable blocks with freeze
+    FixEdgeBugs,
+
+    /// place the finish room at the final walker position
+    FinishRoom,
+
+    /// lock all remaining walker positions
+    FinishWalkerLock,
+
+    /// flood fill starting from the entire main path
+    MainPathFloodFill,
+
+    /// fill up empty blocks that are too far away from the main path
+    FillDeadEnds,
+
+    /// fix stair artifacts resulting from dead end filling
+    FixStairs,
+
+    /// detect and replace the forbidden patterns of the generation config
+    ForbiddenPatterns,
+
+    /// generate skips at corners
+    Skips,
+
+    /// generate platforms along the main path
+    Platforms,
+
+    /// place obstacles in large open areas
+    Obstacles,
+
+    /// ensure that there is a playable path from spawn to finish
+    Validate,
+
+    /// ensure that a tee can actually move from spawn to finish, see MovementModel
+    MovementValidation,
+}
+
+/// results of previous stages that are required by other stages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostInput {
+    FloodFill,
+    MainPathFloodFill,
+    DeadEndBlocks,
+}
+
+impl PostStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostStage::SpawnRoom => "place start room",
+            PostStage::RemoveFreezeBlobs => "detect blobs",
+            PostStage::FloodFill => "flood fill",
+            PostStage::FixEdgeBugs => "fix edge bugs",
+            PostStage::FinishRoom => "place finish room",
+            PostStage::FinishWalkerLock => "finish walker lock",
+            PostStage::MainPathFloodFill => "flood fill (main path dist)",
+            PostStage::FillDeadEnds => "fill dead ends",
+            PostStage::FixStairs => "fix stairs",
+            PostStage::ForbiddenPatterns => "replace forbidden patterns",
+            PostStage::Skips => "generate skips",
+            PostStage::Platforms => "generate platforms",
+            PostStage::Obstacles => "place obstacles",
+            PostStage::Validate => "map path validation",
+            PostStage::MovementValidation => "movement validation",
+        }
+    }
+
+    /// inputs that have to be provided by previous stages
+    pub fn requires(&self) -> &'static [PostInput] {
+        match self {
+            PostStage::FinishRoom
+            | PostStage::MainPathFloodFill
+            | PostStage::Skips
+            | PostStage::Platforms
+            | PostStage::MovementValidation => &[PostInput::FloodFill],
+            PostStage::FillDeadEnds => &[PostInput::MainPathFloodFill],
+            PostStage::FixStairs => &[PostInput::DeadEndBlocks],
+            _ => &[],
+        }
+    }
+
+    /// inputs that are provided for following stages
+    pub fn provides(&self) -> &'static [PostInput] {
+        match self {
+            PostStage::FloodFill => &[PostInput::FloodFill],
+            PostStage::MainPathFloodFill => &[PostInput::MainPathFloodFill],
+            PostStage::FillDeadEnds => &[PostInput::DeadEndBlocks],
+            _ => &[],
+        }
+    }
+
+    /// Runs the stage and stores its results in the context. Stages that are disabled by other
+    /// settings of the generation config (e.g. min_freeze_size = 0) are skipped.
+    pub fn run(
+        &self,
+        gen: &mut Generator,
+        gen_config: &GenerationConfig,
+        thm_config: &ThemeConfig,
+        ctx: &mut PostContext,
+        debug_layers: &mut Option<DebugLayers>,
+    ) -> Result<(), &'static str> {
+        match self {
+            PostStage::SpawnRoom => gen.generate_spawn(thm_config),
+            PostStage::RemoveFreezeBlobs => {
+                if gen_config.min_freeze_size > 0 {
+                    // TODO: Maybe add some alternative function for the case of min_freeze_size=1
+                    post::remove_freeze_blobs(gen, gen_config.min_freeze_size, debug_layers);
+                }
+            }
+            PostStage::FloodFill => {
+                let ff = flood_fill(
+                    &gen.map,
+                    std::slice::from_ref(&gen.spawn),
+                    Some(&gen.walker.pos),
+                    false,
+                )?;
+
+                if let Some(debug_layers) = debug_layers {
+                    *debug_layers.float_grid("flood_fill") =
+                        ff.distance.map(|v| v.map(|v| v as f32));
+                    if let Some(path) = ff.path.as_ref() {
+                        let path_grid = debug_layers.bool_grid("path");
+                        for pos in path {
+                            path_grid[pos.as_index()] = true;
+                        }
+                    }
+                }
+
+                ctx.flood_fill = Some(ff);
+            }
+            PostStage::FixEdgeBugs => {
+                let edge_bugs = post::fix_edge_bugs_expanding(gen)?;
+
+                if let Some(debug_layers) = debug_layers {
+                    let grid = debug_layers.bool_grid("edge_bugs");
+                    grid.zip_mut_with(&edge_bugs, |a, b| *a |= *b);
+                }
+
+                ctx.edge_bugs = Some(edge_bugs);
+            }
+            PostStage::FinishRoom => {
+                post::generate_finish_room(
+                    &gen.walker.pos.clone(),
+                    &mut gen.map,
+                    &gen.walker.locked_positions,
+                    &ctx.get_flood_fill()?.distance,
+                    4,
+                )?;
+            }
+            PostStage::FinishWalkerLock => {
+                gen.walker
+                    .lock_previous_location(&gen.map, gen_config, true)?;
+
+                if let Some(debug_layers) = debug_layers {
+                    *debug_layers.bool_grid("lock") = gen.walker.locked_positions.clone();
+                }
+            }
+            PostStage::MainPathFloodFill => {
+                let main_path = ctx
+                    .get_flood_fill()?
+                    .path
+                    .as_ref()
+                    .ok_or("flood fill has no main path")?;
+                let ff_main_path = flood_fill(&gen.map, main_path, None, true)?;
+
+                if let Some(debug_layers) = debug_layers {
+                    *debug_layers.float_grid("main_path_dist") =
+                        ff_main_path.distance.map(|v| v.map(|v| v as f32));
+                }
+
+                ctx.main_path_flood_fill = Some(ff_main_path);
+            }
+            PostStage::FillDeadEnds => {
+                if gen_config.use_dead_end_removal {
+                    let main_path_distance = &ctx
+                        .main_path_flood_fill
+                        .as_ref()
+                        .ok_or("fill dead ends requires main path flood fill")?
+                        .distance;
+                    ctx.dead_end_blocks = Some(post::fill_dead_ends(
+                        &mut gen.map,
+                        gen_config,
+                        main_path_distance,
+                    )?);
+                }
+            }
+            PostStage::FixStairs => {
+                // nothing to fix if dead end removal is disabled
+                if let Some(dead_end_blocks) = ctx.dead_end_blocks.take() {
+                    post::fix_stairs(&mut gen.map, dead_end_blocks, &mut gen.rnd);
+                }
+            }
+            PostStage::ForbiddenPatterns => {
+                if !gen_config.forbidden_patterns.is_empty() {
+                    let matched = patterns::replace_forbidden_patterns(
+                        &mut gen.map,
+                        &gen_config.forbidden_patterns,
+                        &mut gen.rnd,
+                    )?;
+
+                    if let Some(debug_layers) = debug_layers {
+                        let grid = debug_layers.bool_grid("patterns");
+                        grid.zip_mut_with(&matched, |a, b| *a |= *b);
+                    }
+                }
+            }
+            PostStage::Skips => {
+                ctx.skips = Some(post::generate_all_skips(
+                    gen,
+                    gen_config.skip_length_bounds,
+                    gen_config.skip_min_spacing_sqr,
+                    gen_config.max_level_skip,
+                    &ctx.get_flood_fill()?.distance,
+                    debug_layers,
+                ));
+            }
+            PostStage::Platforms => {
+                let ff = ctx.get_flood_fill()?;
+                let ff_map_length =
+                    ff.distance[gen.walker.pos.as_index()].ok_or("cant determine map length")?;
+
+                let (floor_pos, platforms) = post::generate_platforms(
+                    &mut gen.map,
+                    gen_config,
+                    &ff.distance,
+                    ff_map_length,
+                    debug_layers,
+                )?;
+
+                if let Some(debug_layers) = debug_layers {
+                    let grid = debug_layers.bool_grid("floor");
+                    for floor_pos in floor_pos.iter() {
+                        grid[floor_pos.pos.as_index()] = true;
+                    }
+                }
+
+                ctx.floor_positions = Some(floor_pos);
+                ctx.platforms = Some(platforms);
+            }
+            PostStage::Obstacles => {
+                post::fill_open_areas(gen, &gen_config.max_distance, debug_layers);
+            }
+            PostStage::Validate => {
+                let ff_final = flood_fill(&gen.map, std::slice::from_ref(&gen.spawn), None, false)?;
+                if ff_final.distance[gen.walker.pos.as_index()].is_none() {
+                    return Err("No valid path to finish");
+                }
+            }
+            PostStage::MovementValidation => {
+                // flood fill path goes from finish to spawn
+                let main_path: Option<Vec<Position>> = ctx
+                    .get_flood_fill()?
+                    .path
+                    .as_ref()
+                    .map(|path| path.iter().rev().cloned().collect());
+                let reachability = MovementModel::default().get_reachability(
+                    &gen.map,
+                    &gen.spawn,
+                    &gen.walker.pos,
+                    main_path.as_deref(),
+                );
+
+                if let Some(debug_layers) = debug_layers {
+                    *debug_layers.bool_grid("reachable") = reachability.reachable.clone();
+                    let grid = debug_layers.bool_grid("unreachable");
+                    for (start, end) in reachability.unreachable_sections.iter() {
+                        grid[start.as_index()] = true;
+                        grid[end.as_index()] = true;
+                    }
+                }
+
+                let finish_reachable = reachability.finish_reachable;
+                ctx.reachability = Some(reachability);
+                if !finish_reachable {
+                    return Err("finish not reachable by tee movement");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Post processing stage that is part of a pipeline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PostPass {
+    pub stage: PostStage,
+
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl PostPass {
+    pub fn new(stage: PostStage) -> PostPass {
+        PostPass {
+            stage,
+            enabled: true,
+        }
+    }
+
+    /// the default post processing pipeline
+    pub fn default_pipeline() -> Vec<PostPass> {
+        vec![
+            PostPass::new(PostStage::SpawnRoom),
+            PostPass::new(PostStage::RemoveFreezeBlobs),
+            PostPass::new(PostStage::FloodFill),
+            // we do expanding edge bugs after determining ff, because otherwise it might overlap
+            // end position with new padded freeze.. idk if i like this order tho because many
+            // freeze blocks will now have a ff distance?
+            PostPass::new(PostStage::FixEdgeBugs),
+            PostPass::new(PostStage::FinishRoom),
+            PostPass::new(PostStage::FinishWalkerLock),
+            PostPass::new(PostStage::MainPathFloodFill),
+            PostPass::new(PostStage::FillDeadEnds),
+            PostPass::new(PostStage::FixStairs),
+            PostPass::new(PostStage::ForbiddenPatterns),
+            // TODO: only perform this for updated blocks?
+            PostPass::new(PostStage::FixEdgeBugs),
+            PostPass::new(PostStage::Skips),
+            PostPass::new(PostStage::Platforms),
+            PostPass::new(PostStage::Obstacles),
+            PostPass::new(PostStage::Validate),
+            PostPass {
+                stage: PostStage::MovementValidation,
+                enabled: false,
+            },
+        ]
+    }
+
+    /// enables or disables all passes of a stage
+    pub fn set_stage_enabled(pipeline: &mut [PostPass], stage: PostStage, enabled: bool) {
+        for pass in pipeline.iter_mut().filter(|pass| pass.stage == stage) {
+            pass.enabled = enabled;
+        }
+    }
+
+    /// returns an error if an enabled stage requires an input that no previous stage provides
+    pub fn validate_pipeline(pipeline: &[PostPass]) -> Result<(), &'static str> {
+        let mut provided: Vec<PostInput> = Vec::new();
+
+        for pass in pipeline.iter().filter(|pass| pass.enabled) {
+            if pass
+                .stage
+                .requires()
+                .iter()
+                .any(|input| !provided.contains(input))
+            {
+                return Err("post processing stage is missing inputs of a previous stage");
+            }
+            provided.extend(pass.stage.provides());
+        }
+
+        Ok(())
+    }
+}
+
+/// Results of previous post processing stages
+#[derive(Default, Clone)]
+pub struct PostContext {
+    /// flood fill from spawn, including the main path to the final walker position
+    pub flood_fill: Option<FloodFillResult>,
+
+    /// flood fill starting from the main path
+    pub main_path_flood_fill: Option<FloodFillResult>,
+
+    /// blocks that were filled by the last dead end removal
+    pub dead_end_blocks: Option<Vec<Position>>,
+
+    /// edge bugs that were fixed by the last edge bug fix
+    pub edge_bugs: Option<Array2<bool>>,
+
+    /// floor positions that were considered for platforms
+    pub floor_positions: Option<Vec<FloorPosition>>,
+
+    /// generated skips with the block type they were carved with
+    pub skips: Option<Vec<(Skip, BlockType)>>,
+
+    /// final selection of platforms
+    pub platforms: Option<Vec<PlatformCandidate>>,
+
+    /// result of the movement validation
+    pub reachability: Option<ReachabilityResult>,
+}
+
+impl PostContext {
+    fn get_flood_fill(&self) -> Result<&FloodFillResult, &'static str> {
+        self.flood_fill
+            .as_ref()
+            .ok_or("post processing stage requires flood fill")
+    }
+}
+
+/// Runs the post processing pipeline of a generation config one stage at a time
+#[derive(Default, Clone)]
+pub struct PipelineRunner {
+    pub ctx: PostContext,
+
+    /// index of the next pass in the pipeline
+    pub next_pass: usize,
+
+    /// all stages that have been performed so far, with their duration
+    pub timings: Vec<(PostStage, Duration)>,
+}
+
+impl PipelineRunner {
+    /// next enabled stage that will be performed
+    pub fn get_next_stage(&self, gen_config: &GenerationConfig) -> Option<PostStage> {
+        gen_config.post_processing[self.next_pass.min(gen_config.post_processing.len())..]
+            .iter()
+            .find(|pass| pass.enabled)
+            .map(|pass| pass.stage)
+    }
+
+    pub fn is_finished(&self, gen_config: &GenerationConfig) -> bool {
+        self.get_next_stage(gen_config).is_none()
+    }
+
+    /// Performs the next enabled stage and returns it with its duration, or None if all stages
+    /// are done. If debug layers are used, the blocks changed by the stage are highlighted.
+    pub fn step(
+        &mut self,
+        gen: &mut Generator,
+        gen_config: &GenerationConfig,
+        thm_config: &ThemeConfig,
+        debug_layers: &mut Option<DebugLayers>,
+    ) -> Result<Option<(PostStage, Duration)>, &'static str> {
+        while let Some(pass) = gen_config.post_processing.get(self.next_pass) {
+            self.next_pass += 1;
+            if !pass.enabled {
+                continue;
+            }
+
+            let previous_grid = debug_layers.as_ref().map(|_| gen.map.grid.clone());
+
+            let start = Instant::now();
+            pass.stage
+                .run(gen, gen_config, thm_config, &mut self.ctx, debug_layers)?;
+            let duration = start.elapsed();
+            self.timings.push((pass.stage, duration));
+
+            if let (Some(debug_layers), Some(previous_grid)) = (debug_layers, previous_grid) {
+                set_stage_diff_layers(debug_layers, &previous_grid, &gen.map.grid);
+            }
+
+            return Ok(Some((pass.stage, duration)));
+        }
+
+        Ok(None)
+    }
+}
+
+/// highlights blocks that were added, removed or changed between two map grids
+fn set_stage_diff_layers(
+    debug_layers: &mut DebugLayers,
+    previous_grid: &Array2<BlockType>,
+    grid: &Array2<BlockType>,
+) {
+    let is_empty = |block_type: &BlockType| block_type.to_tw_block_type() == BlockTypeTW::Empty;
+
+    let added = ndarray::Zip::from(previous_grid)
+        .and(grid)
+        .map_collect(|prev, cur| is_empty(prev) && !is_empty(cur));
+    let removed = ndarray::Zip::from(previous_grid)
+        .and(grid)
+        .map_collect(|prev, cur| !is_empty(prev) && is_empty(cur));
+    let changed = ndarray::Zip::from(previous_grid)
+        .and(grid)
+        .map_collect(|prev, cur| prev != cur && is_empty(prev) == is_empty(cur));
+
+    *debug_layers.bool_grid("stage_added") = added;
+    *debug_layers.bool_grid("stage_removed") = removed;
+    *debug_layers.bool_grid("stage_changed") = changed;
+}
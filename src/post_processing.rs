@@ -13,9 +13,11 @@ use std::{
     cmp::Reverse,
     collections::{BinaryHeap, HashMap, VecDeque},
     f32::consts::SQRT_2,
+    fmt,
 };
 
 use dt::dt_bool;
+use log::debug;
 use ndarray::{s, Array2, ArrayBase, Dim, Ix2, ViewRepr};
 
 /// Post processing step to fix all existing edge-bugs, as certain inner/outer kernel
@@ -110,12 +112,7 @@ pub fn fill_open_areas(
     max_distance: &f32,
     debug_layers: &mut Option<DebugLayers>,
 ) -> Array2<f32> {
-    let grid = gen.map.grid.map(|val| *val != BlockType::Empty);
-
-    // euclidean distance transform
-    let distance = dt_bool::<f32>(&grid.into_dyn())
-        .into_dimensionality::<Ix2>()
-        .unwrap();
+    let distance = distance_transform(&gen.map, |val| *val != BlockType::Empty);
 
     if let Some(debug_layers) = debug_layers {
         debug_layers.float_layers.get_mut("dt").unwrap().grid =
@@ -140,6 +137,15 @@ pub fn fill_open_areas(
     distance
 }
 
+/// euclidean distance of each block to the closest blocked block, zero for blocked blocks
+pub fn distance_transform(map: &Map, is_blocked: impl Fn(&BlockType) -> bool) -> Array2<f32> {
+    let grid = map.grid.map(is_blocked);
+
+    dt_bool::<f32>(&grid.into_dyn())
+        .into_dimensionality::<Ix2>()
+        .unwrap()
+}
+
 // returns a vec of corner candidates and their respective direction to the wall
 pub fn find_corners(gen: &Generator) -> Result<Vec<(Position, ShiftDirection)>, &'static str> {
     let mut candidates: Vec<(Position, ShiftDirection)> = Vec::new();
@@ -271,12 +277,12 @@ pub fn remove_unused_blocks(map: &mut Map, position_lock: &Array2<bool>) {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Skip {
-    start_pos: Position,
-    end_pos: Position,
-    length: usize,
-    direction: ShiftDirection,
+    pub start_pos: Position,
+    pub end_pos: Position,
+    pub length: usize,
+    pub direction: ShiftDirection,
 }
 
 /// if a skip has been found, this returns the end position and length
@@ -444,7 +450,7 @@ pub fn generate_all_skips(
     max_level_skip: usize,
     flood_fill: &Array2<Option<usize>>,
     debug_layers: &mut Option<DebugLayers>,
-) {
+) -> Vec<(Skip, BlockType)> {
     // get corner candidates
     let corner_candidates = find_corners(gen).expect("corner detection failed");
 
@@ -508,14 +514,15 @@ pub fn generate_all_skips(
     }
 
     // generate all remaining valid skips
+    let mut generated_skips = Vec::new();
     for skip_index in 0..skips.len() {
-        match skip_status[skip_index] {
-            SkipStatus::Valid => generate_skip(gen, &skips[skip_index], &BlockType::Empty),
-            SkipStatus::ValidFreezeSkipOnly => {
-                generate_skip(gen, &skips[skip_index], &BlockType::Freeze)
-            }
-            _ => (),
-        }
+        let block_type = match skip_status[skip_index] {
+            SkipStatus::Valid => BlockType::Empty,
+            SkipStatus::ValidFreezeSkipOnly => BlockType::Freeze,
+            SkipStatus::Invalid => continue,
+        };
+        generate_skip(gen, &skips[skip_index], &block_type);
+        generated_skips.push((skips[skip_index].clone(), block_type));
     }
 
     // add debug visualizations
@@ -533,6 +540,8 @@ pub fn generate_all_skips(
             debug_layer.grid[skip.end_pos.as_index()] = true;
         }
     }
+
+    generated_skips
 }
 
 pub fn get_window<T>(
@@ -653,15 +662,15 @@ pub fn remove_freeze_blobs(
 
             // unconnected blob has been found
             if blob_unconnected {
-                if let Some(debug_layers) = debug_layers {
-                    for visited_pos in blob_visited {
+                for visited_pos in blob_visited {
+                    if let Some(debug_layers) = debug_layers {
                         debug_layers.bool_layers.get_mut("blobs").unwrap().grid
                             [visited_pos.as_index()] = true;
+                    }
 
-                        // remove small blobs
-                        if blob_size < min_freeze_size {
-                            gen.map.grid[visited_pos.as_index()] = BlockType::Empty;
-                        }
+                    // remove small blobs
+                    if blob_size < min_freeze_size {
+                        gen.map.grid[visited_pos.as_index()] = BlockType::Empty;
                     }
                 }
             }
@@ -669,6 +678,7 @@ pub fn remove_freeze_blobs(
     }
 }
 
+#[derive(Clone)]
 pub struct FloodFillResult {
     /// tracks distance from each cell to `start_pos`
     pub distance: Array2<Option<usize>>,
@@ -683,13 +693,13 @@ pub struct FloodFillResult {
 /// flood fill implementation with optional multi-start or direction tracking.
 /// to enable direction tracking, just provide an `end_pos`
 pub fn flood_fill(
-    gen: &Generator,
+    map: &Map,
     start_pos: &[Position],
     end_pos: Option<&Position>,
     fill_freeze: bool,
 ) -> Result<FloodFillResult, &'static str> {
-    let width = gen.map.width;
-    let height = gen.map.height;
+    let width = map.width;
+    let height = map.height;
     let mut distance = Array2::from_elem((width, height), None);
     let mut queue = VecDeque::new();
 
@@ -700,8 +710,7 @@ pub fn flood_fill(
         None
     };
 
-    let blocked_positions = gen
-        .map
+    let blocked_positions = map
         .grid
         .map(|val| val.is_solid() || (!fill_freeze && val.is_freeze()));
 
@@ -723,8 +732,8 @@ pub fn flood_fill(
         ];
 
         for shift in shifts.iter() {
-            let pos_neighbor = pos.shifted(shift, &gen.map)?;
-            if gen.map.pos_in_bounds(&pos_neighbor)
+            let pos_neighbor = pos.shifted(shift, map)?;
+            if map.pos_in_bounds(&pos_neighbor)
                 && !blocked_positions[pos_neighbor.as_index()]
                 && distance[pos_neighbor.as_index()].is_none()
             {
@@ -744,12 +753,12 @@ pub fn flood_fill(
         let mut pos = end_pos.clone();
         let num_steps = distance[pos.as_index()].unwrap();
         let from = come_from.as_ref().unwrap();
-        let mut path_grid: Array2<bool> = Array2::from_elem((gen.map.width, gen.map.height), false);
+        let mut path_grid: Array2<bool> = Array2::from_elem((map.width, map.height), false);
         let mut path: Vec<Position> = vec![end_pos.clone()];
 
         for _ in 0..num_steps {
             let shift = from[pos.as_index()].unwrap().get_opposite();
-            pos.shift_inplace(&shift, &gen.map)?;
+            pos.shift_inplace(&shift, map)?;
             path_grid[pos.as_index()] = true;
             path.push(pos.clone());
         }
@@ -866,19 +875,6 @@ pub fn generate_noise_layers(
     }
 }
 
-/// prototype for general purpose pattern detection. I dont need this right now, but i'll leave it
-/// here for future me :)
-// pub fn detect_pattern(map: &mut Map) {
-//     todo!();
-//     type BlockTypePredicate = fn(&BlockType) -> bool;
-//     #[rustfmt::skip]
-//     const PATTERN: [[BlockTypePredicate; 3]; 3] = [
-//         [BlockType::is_empty, BlockType::is_solid, BlockType::is_empty],
-//         [BlockType::is_solid, BlockType::is_empty, BlockType::is_solid],
-//         [BlockType::is_empty, BlockType::is_solid, BlockType::is_empty],
-//     ];
-// }
-
 /// Fix diagonal staircase patterns
 ///
 /// using cityblock distance based floodfill for dead-end removal results in 'perfect' staircases,
@@ -1076,7 +1072,7 @@ enum PlatformPosCandidate {
     Grouped(usize),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FloorPosition {
     pub pos: Position,
     pub empty_height: usize,
@@ -1485,7 +1481,7 @@ pub fn generate_platforms(
     flood_fill: &Array2<Option<usize>>,
     ff_map_length: usize,
     debug_layers: &mut Option<DebugLayers>,
-) -> Result<Vec<FloorPosition>, &'static str> {
+) -> Result<(Vec<FloorPosition>, Vec<PlatformCandidate>), &'static str> {
     // find potential floor positions
     let floor_pos = find_floor_positions(map, gen_config)?;
 
@@ -1533,25 +1529,93 @@ pub fn generate_platforms(
     // check that no platform gap is too large
     // TODO: this doesnt yet consider multi-path maps
     final_platforms.sort_unstable_by(|a, b| a.flood_fill_dist.cmp(&b.flood_fill_dist));
-    let ff_gaps: Vec<usize> = final_platforms
-        .windows(2)
-        .map(|a| a[1].flood_fill_dist - a[0].flood_fill_dist)
-        .collect();
-    // TODO: introduce these as a parameter?
-    // dbg!(&ff_gaps);
-    let max_valid_gap = (gen_config.plat_target_distance as f32 * 1.50) as usize;
-    let min_valid_gap = (gen_config.plat_target_distance as f32 / 2.00) as usize;
-    let max_gap = *ff_gaps.iter().max().unwrap();
-    let min_gap = *ff_gaps.iter().min().unwrap();
-    if max_gap > max_valid_gap {
-        // dbg!(min_valid_gap, max_valid_gap, min_gap, max_gap);
-        return Err("maximum plat gap constrain not fulfilled");
-    } else if min_gap < min_valid_gap {
-        // dbg!(min_valid_gap, max_valid_gap, min_gap, max_gap);
-        return Err("minimum plat gap constrain not fulfilled");
+    let gap_report = PlatformGapReport::new(&final_platforms, gen_config);
+    debug!("platform gaps:\n{}", gap_report);
+
+    if let Some(debug_layers) = debug_layers {
+        let grid = debug_layers.float_grid("plat_gap");
+        for (plat, deviation) in final_platforms.iter().skip(1).zip(gap_report.deviations()) {
+            grid[plat.pos.as_index()] = Some(deviation);
+        }
     }
 
-    Ok(floor_pos)
+    gap_report.validate()?;
+
+    Ok((floor_pos, final_platforms))
+}
+
+/// Gaps between consecutive platforms, given the flood fill distances of all platforms in any
+/// order. Used by platform placement, map metrics and thereby the acceptance criteria.
+pub fn get_platform_gaps(flood_fill_dists: impl IntoIterator<Item = usize>) -> Vec<usize> {
+    let mut dists: Vec<usize> = flood_fill_dists.into_iter().collect();
+    dists.sort_unstable();
+    dists.windows(2).map(|d| d[1] - d[0]).collect()
+}
+
+/// Gaps between consecutive platforms, measured as flood fill distance, and the gap bounds
+/// derived from the generation config
+#[derive(Debug, Clone)]
+pub struct PlatformGapReport {
+    pub target_gap: usize,
+    pub min_valid_gap: usize,
+    pub max_valid_gap: usize,
+    pub gaps: Vec<usize>,
+}
+
+impl PlatformGapReport {
+    /// platforms are expected to be sorted by their flood fill distance, so the gaps match them
+    pub fn new(platforms: &[PlatformCandidate], gen_config: &GenerationConfig) -> Self {
+        let target_gap = gen_config.plat_target_distance;
+
+        PlatformGapReport {
+            target_gap,
+            min_valid_gap: (target_gap as f32 * gen_config.plat_min_gap_factor) as usize,
+            max_valid_gap: (target_gap as f32 * gen_config.plat_max_gap_factor) as usize,
+            gaps: get_platform_gaps(platforms.iter().map(|plat| plat.flood_fill_dist)),
+        }
+    }
+
+    /// deviation of each gap relative to the target gap, e.g. 0.5 for a 50% larger gap
+    pub fn deviations(&self) -> impl Iterator<Item = f32> + '_ {
+        self.gaps
+            .iter()
+            .map(|gap| (*gap as f32 - self.target_gap as f32) / self.target_gap.max(1) as f32)
+    }
+
+    /// With less than two platforms there are no gaps, so nothing can be violated
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.gaps.iter().any(|gap| *gap > self.max_valid_gap) {
+            return Err("maximum plat gap constrain not fulfilled");
+        }
+        if self.gaps.iter().any(|gap| *gap < self.min_valid_gap) {
+            return Err("minimum plat gap constrain not fulfilled");
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for PlatformGapReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "target {} (valid {} - {}), {} gaps",
+            self.target_gap,
+            self.min_valid_gap,
+            self.max_valid_gap,
+            self.gaps.len()
+        )?;
+        for (gap, deviation) in self.gaps.iter().zip(self.deviations()) {
+            let marker = match *gap {
+                gap if gap > self.max_valid_gap => " too large",
+                gap if gap < self.min_valid_gap => " too small",
+                _ => "",
+            };
+            write!(f, "\n  {:>5} ({:+.0}%){}", gap, deviation * 100.0, marker)?;
+        }
+
+        Ok(())
+    }
 }
 
 pub fn set_platform(
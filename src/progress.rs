@@ -0,0 +1,101 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::pipeline::PostStage;
+
+/// how many walker steps are performed between two progress reports
+pub const PROGRESS_STEP_INTERVAL: usize = 1000;
+
+/// stage of a map generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GenerationStage {
+    Walker,
+    PostProcessing(PostStage),
+    ExportPreprocess,
+    Finished,
+}
+
+impl GenerationStage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GenerationStage::Walker => "walker",
+            GenerationStage::PostProcessing(stage) => stage.name(),
+            GenerationStage::ExportPreprocess => "export preprocess",
+            GenerationStage::Finished => "finished",
+        }
+    }
+}
+
+/// durations of all performed stages of a generation attempt, in order of execution
+pub type StageTimings = Vec<(GenerationStage, Duration)>;
+
+/// Progress of a map generation, passed to the observer of GeneratorBuilder::generate_observed.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    pub stage: GenerationStage,
+
+    /// index of the current attempt, if failed generations are retried
+    pub attempt: usize,
+
+    /// performed walker steps
+    pub steps: usize,
+
+    /// index of the waypoint the walker is currently walking towards
+    pub waypoint_index: usize,
+
+    pub waypoint_count: usize,
+
+    /// time since generation started
+    pub elapsed: Duration,
+}
+
+/// Allows aborting a running generation, either manually via cancel() or after a deadline.
+/// Clones share the same cancellation state, so a clone can be moved to another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// token that is cancelled automatically once timeout has passed
+    pub fn with_timeout(timeout: Duration) -> CancellationToken {
+        CancellationToken {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            deadline: Some(Instant::now() + timeout),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_deadline_exceeded(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// returns an error if the generation should be aborted
+    pub fn check(&self) -> Result<(), &'static str> {
+        if self.is_cancelled() {
+            Err("generation cancelled")
+        } else if self.is_deadline_exceeded() {
+            Err("generation deadline exceeded")
+        } else {
+            Ok(())
+        }
+    }
+}
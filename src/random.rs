@@ -2,10 +2,10 @@ use crate::position::ShiftDirection;
 use crate::{config::GenerationConfig, editor::SeedType};
 use base64::{engine::general_purpose::URL_SAFE, Engine as _};
 use rand::prelude::*;
-use rand::rngs::SmallRng;
 use rand_distr::WeightedAliasIndex;
+use rand_xoshiro::Xoshiro256PlusPlus;
 use seahash::hash;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct RandomDistConfig<T> {
@@ -45,13 +45,81 @@ impl<T> RandomDistConfig<T> {
     {
         self.values.as_ref()?.iter().max()
     }
+
+    /// Linear interpolation between two distributions. If both distributions have values, the
+    /// union of both value sets is used and the probabilities are blended per value. Otherwise
+    /// probabilities are blended element-wise, which requires the same amount of elements.
+    pub fn lerp(&self, other: &RandomDistConfig<T>, weight: f32) -> RandomDistConfig<T>
+    where
+        T: Clone + PartialEq,
+    {
+        let mut result = match (&self.values, &other.values) {
+            (Some(values), Some(other_values)) => {
+                let mut values = values.clone();
+                let mut probs: Vec<f32> = self.probs.iter().map(|p| p * (1.0 - weight)).collect();
+
+                for (value, prob) in other_values.iter().zip(other.probs.iter()) {
+                    match values.iter().position(|v| v == value) {
+                        Some(index) => probs[index] += prob * weight,
+                        None => {
+                            values.push(value.clone());
+                            probs.push(prob * weight);
+                        }
+                    }
+                }
+
+                // drop values that cant be sampled anymore
+                let (values, probs) = values
+                    .into_iter()
+                    .zip(probs)
+                    .filter(|(_, prob)| *prob > 0.0)
+                    .unzip();
+
+                RandomDistConfig::new(Some(values), probs)
+            }
+            _ if self.probs.len() == other.probs.len() => RandomDistConfig::new(
+                self.values.clone(),
+                self.probs
+                    .iter()
+                    .zip(other.probs.iter())
+                    .map(|(p1, p2)| p1 * (1.0 - weight) + p2 * weight)
+                    .collect(),
+            ),
+            // distributions cant be blended, so just pick the closer one
+            _ if weight < 0.5 => self.clone(),
+            _ => other.clone(),
+        };
+
+        result.normalize_probs();
+        result
+    }
 }
 
+#[derive(Clone)]
 pub struct RandomDist<T> {
     rnd_cfg: RandomDistConfig<T>,
     rnd_dist: WeightedAliasIndex<f32>,
 }
 
+/// only the config is stored, the distribution is rebuilt when deserializing
+impl<T: Serialize> Serialize for RandomDist<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rnd_cfg.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de> + Clone> Deserialize<'de> for RandomDist<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = RandomDistConfig::deserialize(deserializer)?;
+        WeightedAliasIndex::new(config.probs.clone())
+            .map(|rnd_dist| RandomDist {
+                rnd_cfg: config,
+                rnd_dist,
+            })
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 pub enum RandomDistType {
     InnerSize,
     OuterMargin,
@@ -68,9 +136,11 @@ impl<T: Clone> RandomDist<T> {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Random {
     pub seed: Seed,
-    gen: SmallRng,
+    /// concrete xoshiro instead of SmallRng, so the serialized state is platform independent
+    gen: Xoshiro256PlusPlus,
     shift_dist: RandomDist<ShiftDirection>,
     inner_kernel_size_dist: RandomDist<usize>,
     outer_kernel_margin_dist: RandomDist<usize>,
@@ -78,7 +148,7 @@ pub struct Random {
 }
 
 /// u64 seed wrapper with various conversion methods
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Seed {
     pub seed_u64: u64,
 }
@@ -127,9 +197,27 @@ impl Seed {
 }
 
 impl Random {
+    /// Seeds the xoshiro state using PCG32 like rand's SmallRng does, which differs from
+    /// rand_xoshiro's seed_from_u64. This way seeds keep generating the same maps.
+    fn get_xoshiro(seed_u64: u64) -> Xoshiro256PlusPlus {
+        const MUL: u64 = 6364136223846793005;
+        const INC: u64 = 11634580027462260723;
+
+        let mut state = seed_u64;
+        let mut seed = [0u8; 32];
+        for chunk in seed.chunks_mut(4) {
+            state = state.wrapping_mul(MUL).wrapping_add(INC);
+            let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+            let rot = (state >> 59) as u32;
+            chunk.copy_from_slice(&xorshifted.rotate_right(rot).to_le_bytes());
+        }
+
+        Xoshiro256PlusPlus::from_seed(seed)
+    }
+
     pub fn new(seed: Seed, config: &GenerationConfig) -> Random {
         Random {
-            gen: SmallRng::seed_from_u64(seed.seed_u64),
+            gen: Random::get_xoshiro(seed.seed_u64),
             seed,
             shift_dist: RandomDist::new(config.shift_weights.clone()),
             outer_kernel_margin_dist: RandomDist::new(config.outer_margin_probs.clone()),
@@ -139,6 +227,14 @@ impl Random {
         }
     }
 
+    /// rebuild all distributions based on the given config, without affecting the PRNG state
+    pub fn update_dists(&mut self, config: &GenerationConfig) {
+        self.shift_dist = RandomDist::new(config.shift_weights.clone());
+        self.outer_kernel_margin_dist = RandomDist::new(config.outer_margin_probs.clone());
+        self.inner_kernel_size_dist = RandomDist::new(config.inner_size_probs.clone());
+        self.circ_dist = RandomDist::new(config.circ_probs.clone());
+    }
+
     pub fn sample_inner_kernel_size(&mut self) -> usize {
         let dist = &self.inner_kernel_size_dist;
         let index = dist.rnd_dist.sample(&mut self.gen);
@@ -163,6 +259,12 @@ impl Random {
         *ordered_shifts.get(index).unwrap()
     }
 
+    /// sample a value from a distribution that is not part of the generation config
+    pub fn sample_dist<T: Clone>(&mut self, dist: &RandomDist<T>) -> T {
+        let index = dist.rnd_dist.sample(&mut self.gen);
+        dist.rnd_cfg.values.as_ref().unwrap()[index].clone()
+    }
+
     /// derive a u64 seed from entropy
     pub fn get_u64_from_entropy() -> u64 {
         let mut tmp_rng = SmallRng::from_entropy();
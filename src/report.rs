@@ -0,0 +1,156 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::MapConfig, generator::Generator, map::Map, pipeline::PostContext, position::Position,
+    transform::ExportTransform,
+};
+
+/// skip that was carved into the map
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SkipReport {
+    pub start: Position,
+    pub end: Position,
+    pub length: usize,
+
+    /// skip was filled with freeze instead of being fully opened
+    pub freeze_only: bool,
+}
+
+/// platform that was placed along the main path
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PlatformReport {
+    /// leftmost and rightmost block of the platform floor in the generated map. After
+    /// apply_export both are positions in the exported map, so a transform may swap them.
+    pub left: Position,
+    pub right: Position,
+
+    /// flood fill distance from spawn
+    pub flood_fill_dist: usize,
+}
+
+/// Route information of a generated map, so external tools don't have to recompute it. All
+/// positions are relative to the final map, see apply_export.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GenerationReport {
+    pub width: usize,
+    pub height: usize,
+
+    /// length of the map config waypoints, see MapConfig::get_map_length
+    pub map_length: f32,
+
+    pub spawn: Position,
+
+    /// final walker position, where the finish is placed
+    pub finish: Position,
+
+    /// subwaypoints the walker followed
+    pub waypoints: Vec<Position>,
+
+    /// every position of the walker, in order
+    pub position_history: Vec<Position>,
+
+    /// shortest path from spawn to finish, determined by flood fill
+    pub main_path: Vec<Position>,
+
+    pub skips: Vec<SkipReport>,
+    pub platforms: Vec<PlatformReport>,
+
+    /// first and last block of main path sections a tee can't reach, only determined by the
+    /// movement validation stage
+    pub unreachable_sections: Vec<(Position, Position)>,
+}
+
+impl GenerationReport {
+    /// collects the report after post processing, results of disabled stages remain empty
+    pub fn new(gen: &Generator, map_config: &MapConfig, ctx: &PostContext) -> GenerationReport {
+        let main_path = ctx
+            .flood_fill
+            .as_ref()
+            .and_then(|ff| ff.path.as_ref())
+            .map(|path| path.iter().rev().cloned().collect())
+            .unwrap_or_default();
+
+        let skips = ctx
+            .skips
+            .iter()
+            .flatten()
+            .map(|(skip, block_type)| SkipReport {
+                start: skip.start_pos.clone(),
+                end: skip.end_pos.clone(),
+                length: skip.length,
+                freeze_only: block_type.is_freeze(),
+            })
+            .collect();
+
+        let platforms = ctx
+            .platforms
+            .iter()
+            .flatten()
+            .map(|plat| PlatformReport {
+                left: Position::new(plat.pos.x - plat.offset_left, plat.pos.y),
+                right: Position::new(plat.pos.x + plat.offset_right, plat.pos.y),
+                flood_fill_dist: plat.flood_fill_dist,
+            })
+            .collect();
+
+        let unreachable_sections = ctx
+            .reachability
+            .as_ref()
+            .map(|reachability| reachability.unreachable_sections.clone())
+            .unwrap_or_default();
+
+        GenerationReport {
+            width: gen.map.width,
+            height: gen.map.height,
+            map_length: map_config.get_map_length(),
+            spawn: gen.spawn.clone(),
+            finish: gen.walker.pos.clone(),
+            waypoints: gen.walker.waypoints.clone(),
+            position_history: gen.walker.position_history.clone(),
+            main_path,
+            skips,
+            platforms,
+            unreachable_sections,
+        }
+    }
+
+    /// maps all positions to the exported map, after export preprocessing
+    pub fn apply_export(&mut self, export: &ExportTransform, map: &Map) {
+        let apply = |positions: &mut Vec<Position>| {
+            for pos in positions.iter_mut() {
+                *pos = export.apply_pos(pos);
+            }
+        };
+
+        self.width = map.width;
+        self.height = map.height;
+        self.spawn = export.apply_pos(&self.spawn);
+        self.finish = export.apply_pos(&self.finish);
+        apply(&mut self.waypoints);
+        apply(&mut self.position_history);
+        apply(&mut self.main_path);
+
+        for skip in self.skips.iter_mut() {
+            skip.start = export.apply_pos(&skip.start);
+            skip.end = export.apply_pos(&skip.end);
+        }
+
+        for plat in self.platforms.iter_mut() {
+            plat.left = export.apply_pos(&plat.left);
+            plat.right = export.apply_pos(&plat.right);
+        }
+
+        for (start, end) in self.unreachable_sections.iter_mut() {
+            *start = export.apply_pos(start);
+            *end = export.apply_pos(end);
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), &'static str> {
+        let serialized =
+            serde_json::to_string_pretty(self).map_err(|_| "failed to serialize report")?;
+        fs::write(path, serialized).map_err(|_| "failed to write report")
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+use crate::random::Seed;
+
+/// where a generation attempt failed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// invalid configs or layout generation
+    Config,
+    Walker,
+    PostProcessing,
+    Panic,
+    Cancelled,
+
+    /// successful generation that violates the acceptance criteria
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttemptFailure {
+    pub kind: FailureKind,
+    pub error: &'static str,
+}
+
+impl AttemptFailure {
+    pub fn new(kind: FailureKind, error: &'static str) -> AttemptFailure {
+        AttemptFailure { kind, error }
+    }
+}
+
+/// a single generation attempt and why it failed, if it did
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    pub index: usize,
+    pub seed: Seed,
+    pub failure: Option<AttemptFailure>,
+}
+
+/// all attempts of a failed generation
+#[derive(Debug, Clone)]
+pub struct GenerationFailure {
+    pub attempts: Vec<Attempt>,
+}
+
+impl GenerationFailure {
+    /// failure of the last attempt, which stopped the generation
+    pub fn last_failure(&self) -> &AttemptFailure {
+        self.attempts
+            .last()
+            .and_then(|attempt| attempt.failure.as_ref())
+            .expect("failed generation has at least one failed attempt")
+    }
+
+    pub fn error(&self) -> &'static str {
+        self.last_failure().error
+    }
+}
+
+/// Defines how failed generations are retried. Retry seeds only depend on the original seed
+/// and the attempt index, so the final map can be reproduced from the original seed alone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// maximum amount of attempts, including the first one
+    pub max_attempts: usize,
+
+    /// failure kinds that are retried, cancelled generations are never retried
+    pub retryable: Vec<FailureKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            retryable: vec![
+                FailureKind::Walker,
+                FailureKind::PostProcessing,
+                FailureKind::Panic,
+                FailureKind::Rejected,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// policy that never retries
+    pub fn no_retry() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn with_retryable(mut self, retryable: Vec<FailureKind>) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// whether another attempt should follow the failed attempt with the given index
+    pub fn should_retry(&self, failure: &AttemptFailure, attempt_index: usize) -> bool {
+        failure.kind != FailureKind::Cancelled
+            && self.retryable.contains(&failure.kind)
+            && attempt_index + 1 < self.max_attempts
+    }
+
+    /// Seed of the attempt with the given index. The first attempt uses the original seed, all
+    /// further seeds are derived by hashing the original seed and the attempt index.
+    pub fn get_attempt_seed(original_seed: &Seed, attempt_index: usize) -> Seed {
+        if attempt_index == 0 {
+            return original_seed.clone();
+        }
+
+        let mut bytes = original_seed.seed_u64.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(attempt_index as u64).to_le_bytes());
+        Seed::from_u64(seahash::hash(&bytes))
+    }
+}
@@ -0,0 +1,174 @@
+use std::fs;
+
+use clap::crate_version;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{GenerationConfig, MapConfig, ThemeConfig},
+    generator::Generator,
+    pipeline::PipelineRunner,
+};
+
+/// Copy of the entire generator state at a certain point of the generation. Debug layers are
+/// not part of a snapshot.
+#[derive(Clone)]
+pub struct Snapshot {
+    /// walker steps at the time of the snapshot
+    pub walker_steps: usize,
+
+    /// amount of performed post processing stages
+    pub post_stages: usize,
+
+    pub gen: Generator,
+    pub post_runner: Option<PipelineRunner>,
+}
+
+impl Snapshot {
+    pub fn new(gen: &Generator, post_runner: &Option<PipelineRunner>) -> Snapshot {
+        Snapshot {
+            walker_steps: gen.walker.steps,
+            post_stages: post_runner
+                .as_ref()
+                .map_or(0, |runner| runner.timings.len()),
+            gen: gen.clone(),
+            post_runner: post_runner.clone(),
+        }
+    }
+
+    pub fn get_label(&self) -> String {
+        if self.post_stages > 0 {
+            format!("post stage {}", self.post_stages)
+        } else {
+            format!("step {}", self.walker_steps)
+        }
+    }
+}
+
+/// Keeps periodic snapshots of the generator state. To limit the memory usage, every second
+/// walker step snapshot is dropped and the interval is doubled once max_snapshots is reached.
+pub struct Timeline {
+    pub snapshots: Vec<Snapshot>,
+
+    /// walker steps between two snapshots
+    pub step_interval: usize,
+
+    /// initial step interval, restored when the timeline is cleared
+    pub initial_step_interval: usize,
+
+    /// maximum number of walker step snapshots
+    pub max_snapshots: usize,
+
+    /// index of the snapshot that was restored last, if generation did not continue since then
+    pub cursor: Option<usize>,
+}
+
+impl Timeline {
+    pub fn new(step_interval: usize, max_snapshots: usize) -> Timeline {
+        Timeline {
+            snapshots: Vec::new(),
+            step_interval,
+            initial_step_interval: step_interval,
+            max_snapshots,
+            cursor: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.step_interval = self.initial_step_interval;
+        self.cursor = None;
+    }
+
+    /// records a snapshot if the walker reached the next step interval
+    pub fn record_step(&mut self, gen: &Generator) {
+        self.discard_after_cursor();
+
+        // not every generation step moves the walker
+        let is_recorded = self
+            .snapshots
+            .last()
+            .is_some_and(|snapshot| snapshot.walker_steps == gen.walker.steps);
+
+        if gen.walker.steps % self.step_interval == 0 && !is_recorded {
+            self.record(gen, &None);
+        }
+    }
+
+    /// Records a snapshot. If a previous snapshot was restored, all snapshots after it are
+    /// discarded, as generation continues from there.
+    pub fn record(&mut self, gen: &Generator, post_runner: &Option<PipelineRunner>) {
+        self.discard_after_cursor();
+        self.snapshots.push(Snapshot::new(gen, post_runner));
+
+        let step_snapshots = self
+            .snapshots
+            .iter()
+            .filter(|snapshot| snapshot.post_stages == 0)
+            .count();
+        if step_snapshots > self.max_snapshots {
+            self.thin_out();
+        }
+    }
+
+    /// generation continues from the restored snapshot, so all later snapshots are outdated
+    fn discard_after_cursor(&mut self) {
+        if let Some(cursor) = self.cursor.take() {
+            self.snapshots.truncate(cursor + 1);
+        }
+    }
+
+    /// drops every second walker step snapshot and doubles the step interval
+    fn thin_out(&mut self) {
+        self.step_interval *= 2;
+        let step_interval = self.step_interval;
+        self.snapshots.retain(|snapshot| {
+            snapshot.post_stages > 0 || snapshot.walker_steps % step_interval == 0
+        });
+    }
+
+    /// returns the snapshot at the given index and remembers it as restore point
+    pub fn restore(&mut self, index: usize) -> Option<&Snapshot> {
+        let snapshot = self.snapshots.get(index)?;
+        self.cursor = Some(index);
+        Some(snapshot)
+    }
+}
+
+/// In-progress generation that can be stored to disk and resumed later, e.g. to debug
+/// failures on another machine. Post processing progress is not part of the state, so it can
+/// only be saved before post processing has started.
+#[derive(Serialize, Deserialize)]
+pub struct GenerationState {
+    /// crate version that saved the state
+    pub version: String,
+
+    pub gen_config: GenerationConfig,
+    pub map_config: MapConfig,
+    pub thm_config: ThemeConfig,
+    pub gen: Generator,
+}
+
+impl GenerationState {
+    pub fn save(&self, path: &str) -> Result<(), &'static str> {
+        let serialized =
+            serde_json::to_string(self).map_err(|_| "failed to serialize generation state")?;
+        fs::write(path, serialized).map_err(|_| "failed to write generation state file")
+    }
+
+    pub fn load(path: &str) -> Result<GenerationState, &'static str> {
+        let serialized =
+            fs::read_to_string(path).map_err(|_| "failed to read generation state file")?;
+        let state: GenerationState = serde_json::from_str(&serialized)
+            .map_err(|_| "failed to deserialize generation state")?;
+
+        if state.version != crate_version!() {
+            warn!(
+                "generation state was saved with version {}, generation might differ",
+                state.version
+            );
+        }
+
+        Ok(state)
+    }
+}
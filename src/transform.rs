@@ -0,0 +1,110 @@
+use ndarray::{Array2, Axis};
+use serde::{Deserialize, Serialize};
+
+use crate::{position::Position, random::Random};
+
+/// Transformation of an entire map, e.g. to add variety before export
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum MapTransform {
+    None,
+
+    /// mirror left and right
+    FlipX,
+
+    /// mirror top and bottom
+    FlipY,
+
+    /// rotate by 90 degrees, swaps width and height
+    Rotate90,
+
+    /// flip on the x axis with a 50% chance
+    #[default]
+    Random,
+}
+
+impl MapTransform {
+    pub fn all() -> [MapTransform; 5] {
+        [
+            MapTransform::None,
+            MapTransform::FlipX,
+            MapTransform::FlipY,
+            MapTransform::Rotate90,
+            MapTransform::Random,
+        ]
+    }
+
+    /// Resolves Random using one value of rnd. The value is consumed for every transform, so
+    /// later random values don't depend on the chosen transform.
+    pub fn resolve(self, rnd: &mut Random) -> MapTransform {
+        let flip = rnd.get_bool_with_prob(0.5);
+
+        match self {
+            MapTransform::Random if flip => MapTransform::FlipX,
+            MapTransform::Random => MapTransform::None,
+            transform => transform,
+        }
+    }
+
+    /// Transforms an array of map shape. None and unresolved Random keep the array unchanged.
+    pub fn apply_array<T: Clone>(&self, array: &mut Array2<T>) {
+        match self {
+            MapTransform::None | MapTransform::Random => {}
+            MapTransform::FlipX => array.invert_axis(Axis(0)),
+            MapTransform::FlipY => array.invert_axis(Axis(1)),
+            MapTransform::Rotate90 => {
+                // (x, y) -> (height - 1 - y, x)
+                let mut rotated = array.t().to_owned();
+                rotated.invert_axis(Axis(0));
+                *array = rotated;
+            }
+        }
+    }
+
+    /// transforms a position of a map with the given size before the transform
+    pub fn apply_pos(&self, pos: &Position, width: usize, height: usize) -> Position {
+        match self {
+            MapTransform::None | MapTransform::Random => pos.clone(),
+            MapTransform::FlipX => Position::new(width - 1 - pos.x, pos.y),
+            MapTransform::FlipY => Position::new(pos.x, height - 1 - pos.y),
+            MapTransform::Rotate90 => Position::new(height - 1 - pos.y, pos.x),
+        }
+    }
+
+    /// map size after the transform
+    pub fn apply_size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            MapTransform::Rotate90 => (height, width),
+            _ => (width, height),
+        }
+    }
+}
+
+/// Transform and crop that were applied by Generator::export_preprocess, allows mapping
+/// positions of the generation to positions in the exported map
+#[derive(Debug, Clone)]
+pub struct ExportTransform {
+    /// resolved transform, never Random
+    pub transform: MapTransform,
+
+    /// map size before the transform
+    pub width: usize,
+    pub height: usize,
+
+    /// top left corner of the cropped area in the transformed map, if the map was cropped
+    pub crop_offset: Option<Position>,
+}
+
+impl ExportTransform {
+    pub fn apply_pos(&self, pos: &Position) -> Position {
+        let pos = self.transform.apply_pos(pos, self.width, self.height);
+
+        match &self.crop_offset {
+            Some(offset) => Position::new(
+                pos.x.saturating_sub(offset.x),
+                pos.y.saturating_sub(offset.y),
+            ),
+            None => pos,
+        }
+    }
+}
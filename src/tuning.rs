@@ -0,0 +1,317 @@
+use std::{fs, time::Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    builder::GeneratorBuilder,
+    config::{GenerationConfig, MapConfig},
+    difficulty::{DifficultyEstimate, DifficultyWeights},
+    metrics::MapMetrics,
+    random::{Random, Seed},
+};
+
+/// Numeric generation config parameter that can be tuned by the optimizer. Integer parameters
+/// are rounded after mutation.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TunableParam {
+    InnerRadMutProb,
+    InnerSizeMutProb,
+    OuterRadMutProb,
+    OuterSizeMutProb,
+    MomentumProb,
+    MaxDistance,
+    WaypointReachedDist,
+    PlatTargetDistance,
+    SkipMinSpacingSqr,
+    MaxLevelSkip,
+    FadeSteps,
+    MaxSubwaypointDist,
+    SubwaypointMaxShiftDist,
+    PosLockMaxDist,
+    DeadEndThreshold,
+}
+
+impl TunableParam {
+    pub fn get(&self, config: &GenerationConfig) -> f32 {
+        match self {
+            TunableParam::InnerRadMutProb => config.inner_rad_mut_prob,
+            TunableParam::InnerSizeMutProb => config.inner_size_mut_prob,
+            TunableParam::OuterRadMutProb => config.outer_rad_mut_prob,
+            TunableParam::OuterSizeMutProb => config.outer_size_mut_prob,
+            TunableParam::MomentumProb => config.momentum_prob,
+            TunableParam::MaxDistance => config.max_distance,
+            TunableParam::WaypointReachedDist => config.waypoint_reached_dist as f32,
+            TunableParam::PlatTargetDistance => config.plat_target_distance as f32,
+            TunableParam::SkipMinSpacingSqr => config.skip_min_spacing_sqr as f32,
+            TunableParam::MaxLevelSkip => config.max_level_skip as f32,
+            TunableParam::FadeSteps => config.fade_steps as f32,
+            TunableParam::MaxSubwaypointDist => config.max_subwaypoint_dist,
+            TunableParam::SubwaypointMaxShiftDist => config.subwaypoint_max_shift_dist,
+            TunableParam::PosLockMaxDist => config.pos_lock_max_dist,
+            TunableParam::DeadEndThreshold => config.dead_end_threshold as f32,
+        }
+    }
+
+    pub fn set(&self, config: &mut GenerationConfig, value: f32) {
+        let int_value = value.round().max(0.0) as usize;
+
+        match self {
+            TunableParam::InnerRadMutProb => config.inner_rad_mut_prob = value,
+            TunableParam::InnerSizeMutProb => config.inner_size_mut_prob = value,
+            TunableParam::OuterRadMutProb => config.outer_rad_mut_prob = value,
+            TunableParam::OuterSizeMutProb => config.outer_size_mut_prob = value,
+            TunableParam::MomentumProb => config.momentum_prob = value,
+            TunableParam::MaxDistance => config.max_distance = value,
+            TunableParam::WaypointReachedDist => config.waypoint_reached_dist = int_value,
+            TunableParam::PlatTargetDistance => config.plat_target_distance = int_value,
+            TunableParam::SkipMinSpacingSqr => config.skip_min_spacing_sqr = int_value,
+            TunableParam::MaxLevelSkip => config.max_level_skip = int_value,
+            TunableParam::FadeSteps => config.fade_steps = int_value,
+            TunableParam::MaxSubwaypointDist => config.max_subwaypoint_dist = value,
+            TunableParam::SubwaypointMaxShiftDist => config.subwaypoint_max_shift_dist = value,
+            TunableParam::PosLockMaxDist => config.pos_lock_max_dist = value,
+            TunableParam::DeadEndThreshold => config.dead_end_threshold = int_value,
+        }
+    }
+}
+
+/// range a parameter is mutated in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParamBounds {
+    pub param: TunableParam,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl ParamBounds {
+    pub fn new(param: TunableParam, min: f32, max: f32) -> ParamBounds {
+        ParamBounds { param, min, max }
+    }
+}
+
+/// map metric a tuned preset should match, averaged over all successful maps
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetMetric {
+    MeanCorridorWidth,
+    MeanPlatformGap,
+    FreezeRatio,
+
+    /// estimated difficulty using the default difficulty weights
+    Difficulty,
+}
+
+impl TargetMetric {
+    /// None if the metric is undefined for the map, e.g. platform gaps without platforms
+    pub fn get(&self, metrics: &MapMetrics) -> Option<f32> {
+        match self {
+            TargetMetric::MeanCorridorWidth => Some(metrics.mean_corridor_width),
+            TargetMetric::MeanPlatformGap => metrics.mean_platform_gap,
+            TargetMetric::FreezeRatio => Some(metrics.freeze_ratio),
+            TargetMetric::Difficulty => {
+                Some(DifficultyEstimate::new(metrics, &DifficultyWeights::default()).difficulty)
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetricTarget {
+    pub metric: TargetMetric,
+    pub target: f32,
+
+    /// penalty for a relative deviation of 100% from the target
+    pub weight: f32,
+}
+
+/// Search space and scoring of the optimizer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct TuningConfig {
+    pub bounds: Vec<ParamBounds>,
+    pub targets: Vec<MetricTarget>,
+
+    /// score for a success rate of 100%
+    pub success_weight: f32,
+
+    /// penalty per time_baseline_ms of average generation time
+    pub time_weight: f32,
+    pub time_baseline_ms: f32,
+
+    /// probability of each parameter to be mutated
+    pub mutation_rate: f32,
+
+    /// maximum mutation of a parameter, relative to its bounds
+    pub mutation_strength: f32,
+}
+
+impl Default for TuningConfig {
+    fn default() -> TuningConfig {
+        TuningConfig {
+            bounds: vec![
+                ParamBounds::new(TunableParam::InnerRadMutProb, 0.0, 1.0),
+                ParamBounds::new(TunableParam::InnerSizeMutProb, 0.0, 1.0),
+                ParamBounds::new(TunableParam::OuterRadMutProb, 0.0, 1.0),
+                ParamBounds::new(TunableParam::OuterSizeMutProb, 0.0, 1.0),
+                ParamBounds::new(TunableParam::MomentumProb, 0.0, 0.5),
+                ParamBounds::new(TunableParam::MaxDistance, 1.5, 5.0),
+                ParamBounds::new(TunableParam::WaypointReachedDist, 50.0, 400.0),
+                ParamBounds::new(TunableParam::PlatTargetDistance, 50.0, 300.0),
+                ParamBounds::new(TunableParam::SkipMinSpacingSqr, 5.0, 200.0),
+                ParamBounds::new(TunableParam::MaxLevelSkip, 20.0, 300.0),
+                ParamBounds::new(TunableParam::FadeSteps, 0.0, 200.0),
+                ParamBounds::new(TunableParam::MaxSubwaypointDist, 10.0, 100.0),
+                ParamBounds::new(TunableParam::SubwaypointMaxShiftDist, 0.0, 20.0),
+                ParamBounds::new(TunableParam::PosLockMaxDist, 5.0, 50.0),
+                ParamBounds::new(TunableParam::DeadEndThreshold, 3.0, 30.0),
+            ],
+            targets: Vec::new(),
+            success_weight: 10.0,
+            time_weight: 1.0,
+            time_baseline_ms: 100.0,
+            mutation_rate: 0.3,
+            mutation_strength: 0.2,
+        }
+    }
+}
+
+impl TuningConfig {
+    pub fn load(path: &str) -> Result<TuningConfig, &'static str> {
+        let serialized = fs::read_to_string(path).map_err(|_| "failed to read tuning config")?;
+        serde_json::from_str(&serialized).map_err(|_| "failed to deserialize tuning config")
+    }
+
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.bounds.iter().any(|bounds| bounds.min > bounds.max) {
+            return Err("parameter bounds must have min <= max");
+        }
+        if self.time_baseline_ms <= 0.0 {
+            return Err("time baseline must be >0");
+        }
+
+        Ok(())
+    }
+
+    /// Mutates each parameter with mutation_rate by up to mutation_strength of its bounds. At
+    /// least one parameter is mutated.
+    pub fn mutate(&self, config: &GenerationConfig, rnd: &mut Random) -> GenerationConfig {
+        let mut mutated = config.clone();
+        if self.bounds.is_empty() {
+            return mutated;
+        }
+
+        let forced_index = rnd.get_usize_in_range(0, self.bounds.len() - 1);
+        for (index, bounds) in self.bounds.iter().enumerate() {
+            if index != forced_index && !rnd.get_bool_with_prob(self.mutation_rate) {
+                continue;
+            }
+
+            let shift = rnd.get_f32_in_range(-1.0, 1.0) * self.mutation_strength;
+            let value = bounds.param.get(&mutated) + shift * (bounds.max - bounds.min);
+            bounds
+                .param
+                .set(&mut mutated, value.clamp(bounds.min, bounds.max));
+        }
+
+        mutated
+    }
+
+    /// uniformly samples all parameters within their bounds
+    pub fn sample(&self, config: &GenerationConfig, rnd: &mut Random) -> GenerationConfig {
+        let mut sampled = config.clone();
+        for bounds in self.bounds.iter() {
+            let value = rnd.get_f32_in_range(bounds.min, bounds.max);
+            bounds.param.set(&mut sampled, value);
+        }
+
+        sampled
+    }
+
+    /// Generates maps for all combinations of map configs and seeds and scores the results
+    pub fn evaluate(
+        &self,
+        gen_config: &GenerationConfig,
+        map_configs: &[MapConfig],
+        seeds: &[u64],
+        max_steps: usize,
+    ) -> Evaluation {
+        let mut success_count = 0;
+        let mut total_time_ms = 0.0;
+        let mut metric_values: Vec<Vec<f32>> = vec![Vec::new(); self.targets.len()];
+
+        if gen_config.validate().is_ok() {
+            for map_config in map_configs.iter() {
+                for seed in seeds.iter() {
+                    let start = Instant::now();
+                    let result = GeneratorBuilder::new()
+                        .with_gen_config(gen_config.clone())
+                        .with_map_config(map_config.clone())
+                        .with_seed(Seed::from_u64(*seed))
+                        .with_max_steps(max_steps)
+                        .with_export_preprocess(false)
+                        .generate();
+                    let Ok(result) = result else {
+                        continue;
+                    };
+
+                    success_count += 1;
+                    total_time_ms += start.elapsed().as_secs_f32() * 1000.0;
+
+                    if self.targets.is_empty() {
+                        continue;
+                    }
+                    let Ok(metrics) = MapMetrics::new(&result.map, &result.report) else {
+                        continue;
+                    };
+                    for (values, target) in metric_values.iter_mut().zip(self.targets.iter()) {
+                        values.extend(target.metric.get(&metrics));
+                    }
+                }
+            }
+        }
+
+        let map_count = map_configs.len() * seeds.len();
+        let success_rate = success_count as f32 / map_count.max(1) as f32;
+        let avg_time_ms = (success_count > 0).then(|| total_time_ms / success_count as f32);
+        let metrics: Vec<Option<f32>> = metric_values
+            .iter()
+            .map(|values| {
+                (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+            })
+            .collect();
+
+        // undefined metrics are penalized like a deviation of 100%
+        let metric_penalty: f32 = self
+            .targets
+            .iter()
+            .zip(metrics.iter())
+            .map(|(target, value)| {
+                let deviation = value.map_or(1.0, |value| {
+                    (value - target.target).abs() / target.target.abs().max(f32::EPSILON)
+                });
+                target.weight * deviation
+            })
+            .sum();
+        let time_penalty = self.time_weight * avg_time_ms.unwrap_or(0.0) / self.time_baseline_ms;
+
+        Evaluation {
+            success_rate,
+            avg_time_ms,
+            metrics,
+            score: self.success_weight * success_rate - time_penalty - metric_penalty,
+        }
+    }
+}
+
+/// results of a tuning candidate, higher scores are better
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub success_rate: f32,
+    pub avg_time_ms: Option<f32>,
+
+    /// average value of each target metric
+    pub metrics: Vec<Option<f32>>,
+
+    pub score: f32,
+}
@@ -1,18 +1,21 @@
 use std::fmt;
 
 use ndarray::{s, Array2};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     config::GenerationConfig,
     debug::DebugLayers,
+    guidance::Guidance,
     kernel::Kernel,
     map::{BlockType, Map, Overwrite},
     position::{Position, ShiftDirection},
     random::Random,
+    transform::MapTransform,
     utils::safe_slice_mut,
 };
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub enum WalkerState {
     Default,
     /// (direction, amount of steps left)
@@ -20,6 +23,7 @@ pub enum WalkerState {
 }
 
 // this walker is indeed very cute
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CuteWalker {
     pub pos: Position,
     pub steps: usize,
@@ -57,6 +61,13 @@ pub struct CuteWalker {
 
     /// pre-calculate locking size
     pub lock_size: usize,
+
+    /// coarse path towards the current goal, only used if guidance is enabled
+    pub guidance: Option<Guidance>,
+
+    /// goal index for which no guidance path was found, so it isn't searched again
+    #[serde(default)]
+    pub guidance_failed_index: Option<usize>,
 }
 
 // TODO: somewhere else i used a cool crate for this -> replace
@@ -90,10 +101,7 @@ impl CuteWalker {
         map: &Map,
         gen_config: &GenerationConfig,
     ) -> CuteWalker {
-        // lock size = maximum possible kernel which is max inner + max margin + 2
-        let lock_size = gen_config.inner_size_probs.max_value().unwrap()
-            + gen_config.outer_margin_probs.max_value().unwrap()
-            + 2;
+        let lock_size = gen_config.get_lock_size();
 
         CuteWalker {
             pos: initial_pos,
@@ -113,6 +121,8 @@ impl CuteWalker {
             position_history: Vec::new(),
             state: WalkerState::Default,
             lock_size,
+            guidance: None,
+            guidance_failed_index: None,
         }
     }
 
@@ -150,6 +160,43 @@ impl CuteWalker {
         Ok(())
     }
 
+    /// Transforms all positions and locks of the walker the same way as the map, which had the
+    /// given size before the transform. The guidance is dropped, as it is recalculated anyway.
+    pub fn transform(&mut self, transform: &MapTransform, width: usize, height: usize) {
+        let apply_pos = |pos: &Position| transform.apply_pos(pos, width, height);
+
+        self.pos = transform.apply_pos(&self.pos, width, height);
+        self.goal = self.goal.as_ref().map(apply_pos);
+        self.waypoints = self.waypoints.iter().map(apply_pos).collect();
+        self.position_history = self.position_history.iter().map(apply_pos).collect();
+        transform.apply_array(&mut self.locked_positions);
+        transform.apply_array(&mut self.locked_waypoint_positions);
+        self.guidance = None;
+    }
+
+    /// Moves all positions into a cropped map with the given top left corner and size.
+    /// Positions outside of the cropped area are clamped to its border.
+    pub fn crop(&mut self, top_left: &Position, width: usize, height: usize) {
+        let crop_pos = |pos: &Position| {
+            Position::new(
+                pos.x.saturating_sub(top_left.x).min(width - 1),
+                pos.y.saturating_sub(top_left.y).min(height - 1),
+            )
+        };
+        let area = s![
+            top_left.x..top_left.x + width,
+            top_left.y..top_left.y + height
+        ];
+
+        self.pos = crop_pos(&self.pos);
+        self.goal = self.goal.as_ref().map(crop_pos);
+        self.waypoints = self.waypoints.iter().map(crop_pos).collect();
+        self.position_history = self.position_history.iter().map(crop_pos).collect();
+        self.locked_positions = self.locked_positions.slice(area).to_owned();
+        self.locked_waypoint_positions = self.locked_waypoint_positions.slice(area).to_owned();
+        self.guidance = None;
+    }
+
     pub fn is_goal_reached(&self, waypoint_reached_dist: &usize) -> Option<bool> {
         self.goal
             .as_ref()
@@ -160,12 +207,18 @@ impl CuteWalker {
         if let Some(next_goal) = self.waypoints.get(self.goal_index + 1) {
             self.goal_index += 1;
             self.goal = Some(next_goal.clone());
+            self.guidance = None;
         } else {
             self.finished = true;
             self.goal = None;
         }
     }
 
+    /// progress along the waypoints in range [0, 1], based on the current goal
+    pub fn get_progress(&self) -> f32 {
+        self.goal_index as f32 / (self.waypoints.len().saturating_sub(1)).max(1) as f32
+    }
+
     pub fn check_platform_at_walker(
         &mut self,
         map: &mut Map,
@@ -278,8 +331,19 @@ impl CuteWalker {
         if self.finished {
             return Err("Walker is finished");
         }
-        let goal = self.goal.as_ref().ok_or("Error: Goal is None")?;
-        let shifts = self.pos.get_rated_shifts(goal, map);
+        let goal = self.goal.clone().ok_or("Error: Goal is None")?;
+
+        // if enabled, rate shifts towards the guidance path instead of the goal
+        let target = if gen_config.use_guidance {
+            self.update_guidance(&goal, map, gen_config, debug_layers);
+            self.guidance
+                .as_ref()
+                .map_or(goal, |guidance| guidance.get_target().clone())
+        } else {
+            goal
+        };
+
+        let shifts = self.pos.get_rated_shifts(&target, map);
         let mut current_shift;
 
         match self.state {
@@ -295,7 +359,8 @@ impl CuteWalker {
                 if self.is_shift_locked(&current_shift, map) {
                     if current_shift == shifts[0] || self.is_shift_locked(&shifts[0], map) {
                         // if current and greedy shift (can be the same) are locked -> unpark the walker
-                        let (unpark_shift, unpark_steps) = self.unpark(25, shifts[0], goal, map)?; // unpark using greedy as target direction
+                        let (unpark_shift, unpark_steps) =
+                            self.unpark(25, shifts[0], &target, map)?; // unpark using greedy as target direction
                         self.state = WalkerState::UnParking(unpark_shift, unpark_steps);
                         return Ok(());
                     } else {
@@ -380,6 +445,38 @@ impl CuteWalker {
         Ok(())
     }
 
+    /// Advances the guidance target along the path. The path is recomputed if there is none yet,
+    /// or if the current target got locked in the meantime. If no path is found, the search is
+    /// only retried for the next goal.
+    fn update_guidance(
+        &mut self,
+        goal: &Position,
+        map: &Map,
+        gen_config: &GenerationConfig,
+        debug_layers: &mut Option<DebugLayers>,
+    ) {
+        if let Some(guidance) = &mut self.guidance {
+            guidance.update_target(&self.pos);
+        }
+
+        let is_valid = self.guidance.as_ref().is_some_and(|guidance| {
+            guidance.target_index == guidance.path.len() - 1
+                || !self.is_pos_locked(guidance.get_target())
+        });
+
+        if !is_valid && self.guidance_failed_index != Some(self.goal_index) {
+            let guidance =
+                Guidance::new(&self.pos, goal, map, gen_config.guidance_cell_size, |pos| {
+                    self.is_pos_locked(pos)
+                });
+            match &guidance {
+                Some(guidance) => guidance.update_debug_layer(debug_layers),
+                None => self.guidance_failed_index = Some(self.goal_index),
+            }
+            self.guidance = guidance;
+        }
+    }
+
     pub fn unpark(
         &self,
         max_distance: usize,
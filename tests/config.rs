@@ -0,0 +1,97 @@
+use gores_mapgen::{
+    config::{DifficultyCurve, GenerationConfig},
+    metrics::AcceptanceCriteria,
+    patterns::{ForbiddenPattern, PatternReplacement},
+    random::RandomDistConfig,
+};
+
+/// config where every field differs from the default config, new fields have to be added here
+fn get_changed_config() -> GenerationConfig {
+    GenerationConfig {
+        name: "changed".to_string(),
+        description: Some("every field differs from the default".to_string()),
+        difficulty: 5.0,
+        version: "2.0".to_string(),
+        inner_rad_mut_prob: 0.75,
+        inner_size_mut_prob: 0.25,
+        outer_rad_mut_prob: 0.75,
+        outer_size_mut_prob: 0.25,
+        shift_weights: RandomDistConfig::new(None, vec![0.7, 0.1, 0.1, 0.1]),
+        plat_target_distance: 300,
+        plat_max_freeze: 4,
+        plat_height: 6,
+        plat_min_width: 5,
+        plat_max_width: 20,
+        plat_part_width: 3,
+        plat_max_gap_factor: 3.0,
+        plat_min_gap_factor: 0.25,
+        momentum_prob: 0.5,
+        max_distance: 6.0,
+        waypoint_reached_dist: 50,
+        inner_size_probs: RandomDistConfig::new(Some(vec![7]), vec![1.0]),
+        outer_margin_probs: RandomDistConfig::new(Some(vec![4]), vec![1.0]),
+        circ_probs: RandomDistConfig::new(Some(vec![0.3]), vec![1.0]),
+        skip_length_bounds: (5, 20),
+        skip_min_spacing_sqr: 90,
+        max_level_skip: 180,
+        min_freeze_size: 10,
+        enable_pulse: true,
+        pulse_straight_delay: 20,
+        pulse_corner_delay: 10,
+        pulse_max_kernel_size: 8,
+        fade_steps: 120,
+        fade_max_size: 12,
+        fade_min_size: 6,
+        max_subwaypoint_dist: 25.0,
+        subwaypoint_max_shift_dist: 10.0,
+        skip_invalid_waypoints: true,
+        pos_lock_max_dist: 40.0,
+        pos_lock_max_delay: 2000,
+        enable_kernel_lock: false,
+        waypoint_lock_distance: 20,
+        use_dead_end_removal: false,
+        dead_end_threshold: 20,
+        use_guidance: true,
+        guidance_cell_size: 12,
+        post_processing: Vec::new(),
+        forbidden_patterns: vec![ForbiddenPattern {
+            name: "pillar".to_string(),
+            rows: vec!["_ X _".to_string()],
+            replacements: RandomDistConfig::new(Some(vec![PatternReplacement::EmptyCenter]), vec![1.0]),
+        }],
+        acceptance: AcceptanceCriteria {
+            min_path_length: Some(100),
+            ..AcceptanceCriteria::default()
+        },
+    }
+}
+
+#[test]
+fn lerp_interpolates_all_fields() {
+    let start = GenerationConfig::default();
+    let end = get_changed_config();
+
+    let start_fields = serde_json::to_value(&start).unwrap();
+    let end_fields = serde_json::to_value(&end).unwrap();
+    let lerp_fields = serde_json::to_value(start.lerp(&end, 1.0)).unwrap();
+
+    // name, description and version identify the preset, so they are kept from the start config
+    for (field, start_value) in start_fields.as_object().unwrap() {
+        if ["name", "description", "version"].contains(&field.as_str()) {
+            assert_eq!(&lerp_fields[field], start_value, "{} changed", field);
+        } else {
+            assert_ne!(&end_fields[field], start_value, "{} has to be changed", field);
+            assert_ne!(&lerp_fields[field], start_value, "{} is not interpolated", field);
+        }
+    }
+}
+
+#[test]
+fn empty_difficulty_curve_is_rejected() {
+    let curve = DifficultyCurve {
+        keyframes: Vec::new(),
+    };
+
+    assert!(curve.validate().is_err());
+    assert!(curve.get_config(0.5).is_err());
+}
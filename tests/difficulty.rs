@@ -0,0 +1,53 @@
+use gores_mapgen::difficulty::{DifficultyFeatures, DifficultyWeights};
+
+/// features that vary independently, as their periods are coprime
+fn get_features(index: usize) -> DifficultyFeatures {
+    DifficultyFeatures {
+        narrow_ratio: (index % 7) as f32 / 7.0,
+        freeze_ratio: (index % 5) as f32 / 5.0,
+        platform_gap: (index % 3) as f32 / 3.0,
+        climb_ratio: (index % 11) as f32 / 11.0,
+        turn_density: (index % 13) as f32 / 13.0,
+    }
+}
+
+fn get_samples(difficulty: impl Fn(&DifficultyFeatures) -> f32) -> Vec<(DifficultyFeatures, f32)> {
+    (0..200)
+        .map(get_features)
+        .map(|features| {
+            let label = difficulty(&features);
+            (features, label)
+        })
+        .collect()
+}
+
+#[test]
+fn fit_recovers_non_negative_weights() {
+    let samples = get_samples(|f| 1.0 + 2.0 * f.freeze_ratio + 0.5 * f.turn_density);
+    let weights = DifficultyWeights::fit(&samples).unwrap();
+
+    assert!((weights.bias - 1.0).abs() < 0.05, "{:?}", weights);
+    assert!((weights.freeze_ratio - 2.0).abs() < 0.05, "{:?}", weights);
+    assert!((weights.turn_density - 0.5).abs() < 0.05, "{:?}", weights);
+    assert!(weights.narrow_ratio.abs() < 0.05, "{:?}", weights);
+    assert!(weights.platform_gap.abs() < 0.05, "{:?}", weights);
+    assert!(weights.climb_ratio.abs() < 0.05, "{:?}", weights);
+}
+
+#[test]
+fn fit_clamps_negative_weights_to_zero() {
+    let samples = get_samples(|f| 1.0 + 2.0 * f.freeze_ratio - 1.5 * f.narrow_ratio);
+    let weights = DifficultyWeights::fit(&samples).unwrap();
+
+    assert_eq!(weights.narrow_ratio, 0.0, "{:?}", weights);
+    assert!(weights.platform_gap >= 0.0, "{:?}", weights);
+    assert!(weights.climb_ratio >= 0.0, "{:?}", weights);
+    assert!(weights.turn_density >= 0.0, "{:?}", weights);
+    assert!((weights.freeze_ratio - 2.0).abs() < 0.1, "{:?}", weights);
+}
+
+#[test]
+fn fit_requires_enough_samples() {
+    let samples = get_samples(|f| f.freeze_ratio);
+    assert!(DifficultyWeights::fit(&samples[..3]).is_err());
+}
@@ -0,0 +1,35 @@
+use gores_mapgen::golden::{get_golden_cases, load_golden_cases, GOLDEN_HASHES_PATH};
+
+#[test]
+#[ignore = "golden hashes have to be blessed first, via `cargo run --bin golden -- --bless`"]
+fn golden_hashes_match() {
+    let path = format!("{}/{}", env!("CARGO_MANIFEST_DIR"), GOLDEN_HASHES_PATH);
+    let cases = load_golden_cases(&path).unwrap();
+
+    let failed: Vec<_> = cases
+        .iter()
+        .map(|case| case.check())
+        .filter(|result| !result.matches_golden())
+        .collect();
+
+    assert!(
+        failed.is_empty(),
+        "golden mismatches (bless intended changes via `cargo run --bin golden -- --bless`): {:#?}",
+        failed
+    );
+}
+
+#[test]
+fn editor_matches_builder() {
+    let failed: Vec<_> = get_golden_cases()
+        .iter()
+        .map(|case| case.check())
+        .filter(|result| !result.is_deterministic())
+        .collect();
+
+    assert!(
+        failed.is_empty(),
+        "editor generation differs from the builder: {:#?}",
+        failed
+    );
+}
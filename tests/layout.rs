@@ -0,0 +1,87 @@
+use gores_mapgen::{
+    config::ThemeConfig,
+    layout::{LayoutConfig, LayoutShape},
+    random::Seed,
+};
+
+#[test]
+fn all_layout_shapes_generate() {
+    let thm_config = ThemeConfig::default();
+
+    for shape in LayoutShape::all() {
+        for turns in 1..=8 {
+            let layout = LayoutConfig {
+                shape,
+                turns,
+                ..LayoutConfig::default()
+            };
+
+            for seed in 0..10 {
+                let map_config = layout
+                    .generate(Seed::from_u64(seed), &thm_config)
+                    .unwrap_or_else(|err| {
+                        panic!("{:?} with {} turns (seed {}): {}", shape, turns, seed, err)
+                    });
+                let case = format!("{:?} with {} turns (seed {})", shape, turns, seed);
+
+                assert_eq!(map_config.waypoints.len(), turns + 2, "{}", case);
+                for (index, waypoint) in map_config.waypoints.iter().enumerate() {
+                    for other in map_config.waypoints[index + 1..].iter() {
+                        assert!(
+                            waypoint.distance(other) >= layout.min_waypoint_dist,
+                            "{}: waypoints too close",
+                            case
+                        );
+                    }
+                }
+
+                let same_seed = layout.generate(Seed::from_u64(seed), &thm_config);
+                assert_eq!(same_seed, Ok(map_config), "{}: not deterministic", case);
+            }
+        }
+    }
+}
+
+#[test]
+fn layouts_reach_target_length() {
+    let thm_config = ThemeConfig::default();
+    let target_length = 3000.0;
+
+    for shape in LayoutShape::all() {
+        for turns in 1..=8 {
+            let layout = LayoutConfig {
+                shape,
+                turns,
+                target_length: Some(target_length),
+                ..LayoutConfig::default()
+            };
+
+            for seed in 0..10 {
+                let length = layout
+                    .generate(Seed::from_u64(seed), &thm_config)
+                    .unwrap()
+                    .get_map_length();
+
+                assert!(
+                    (length - target_length).abs() < 0.05 * target_length,
+                    "{:?} with {} turns (seed {}): length {}",
+                    shape,
+                    turns,
+                    seed,
+                    length
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn layout_margin_has_to_fit_spawn() {
+    let thm_config = ThemeConfig::default();
+    let layout = LayoutConfig {
+        margin: thm_config.spawn_width - 1,
+        ..LayoutConfig::default()
+    };
+
+    assert!(layout.validate(&thm_config).is_err());
+}
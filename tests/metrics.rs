@@ -0,0 +1,51 @@
+use gores_mapgen::{
+    map::{BlockType, Map},
+    metrics::MapMetrics,
+    position::Position,
+    report::GenerationReport,
+};
+
+/// Horizontal corridor from (1, 3) to (18, 3), lined with freeze above and below and enclosed
+/// by hookable blocks. The spawn block is reserved for the spawn room.
+fn get_corridor_map() -> (Map, GenerationReport) {
+    let mut map = Map::new(20, 7, BlockType::Hookable);
+    for x in 1..=18 {
+        map.grid[[x, 2]] = BlockType::Freeze;
+        map.grid[[x, 3]] = BlockType::Empty;
+        map.grid[[x, 4]] = BlockType::Freeze;
+    }
+    map.grid[[1, 3]] = BlockType::EmptyRoom;
+
+    let report = GenerationReport {
+        width: map.width,
+        height: map.height,
+        map_length: 17.0,
+        spawn: Position::new(1, 3),
+        finish: Position::new(18, 3),
+        waypoints: Vec::new(),
+        position_history: Vec::new(),
+        main_path: Vec::new(),
+        skips: Vec::new(),
+        platforms: Vec::new(),
+        unreachable_sections: Vec::new(),
+    };
+
+    (map, report)
+}
+
+#[test]
+fn corridor_metrics() {
+    let (map, report) = get_corridor_map();
+    let metrics = MapMetrics::new(&map, &report).unwrap();
+
+    assert_eq!(metrics.main_path_length, 17);
+
+    // both ends of the corridor touch the wall, everywhere else freeze counts to the width
+    assert_eq!(metrics.corridor_width_histogram, vec![0, 2, 0, 16]);
+
+    // 36 freeze blocks next to 18 open blocks, including the spawn room block
+    assert_eq!(metrics.freeze_ratio, 2.0);
+    assert_eq!(metrics.empty_blocks, 18);
+    assert_eq!(metrics.max_open_distance, 1.0);
+    assert_eq!(metrics.platform_gap_bounds, None);
+}
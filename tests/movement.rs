@@ -0,0 +1,121 @@
+use gores_mapgen::{
+    map::{BlockType, Map},
+    movement::MovementModel,
+    position::Position,
+};
+
+/// map enclosed by hookable blocks, with everything inside the border left empty
+fn get_enclosed_map(width: usize, height: usize) -> Map {
+    let mut map = Map::new(width, height, BlockType::Hookable);
+    for x in 1..width - 1 {
+        for y in 1..height - 1 {
+            map.grid[[x, y]] = BlockType::Empty;
+        }
+    }
+
+    map
+}
+
+/// Floor at y=18 with a step of the given height from x=20 onwards. The main path walks to the
+/// step, climbs it and ends at the finish on top of it.
+fn get_step_map(step_height: usize) -> (Map, Vec<Position>) {
+    let mut map = get_enclosed_map(40, 19);
+    let top = 18 - step_height;
+    for x in 20..=38 {
+        for y in top..=17 {
+            map.grid[[x, y]] = BlockType::Hookable;
+        }
+    }
+
+    let mut main_path: Vec<Position> = (2..=19).map(|x| Position::new(x, 17)).collect();
+    main_path.extend((top - 1..=16).rev().map(|y| Position::new(19, y)));
+    main_path.extend((20..=36).map(|x| Position::new(x, top - 1)));
+
+    (map, main_path)
+}
+
+/// Corridor from y=3 to y=7 under a hookable ceiling. The floor between x=11 and x=39 is
+/// freeze, so the gap can't be jumped.
+fn get_gap_map() -> (Map, Vec<Position>) {
+    let mut map = get_enclosed_map(50, 9);
+    for x in 1..=48 {
+        map.grid[[x, 1]] = BlockType::Hookable;
+        map.grid[[x, 2]] = BlockType::Hookable;
+    }
+    for x in 11..=39 {
+        map.grid[[x, 8]] = BlockType::Freeze;
+    }
+
+    let main_path = (2..=45).map(|x| Position::new(x, 7)).collect();
+
+    (map, main_path)
+}
+
+#[test]
+fn jump_only_corridor() {
+    let model = MovementModel {
+        hook_range: 0,
+        ..MovementModel::default()
+    };
+
+    // ground jump and air jump rise 9 blocks in total
+    let (map, main_path) = get_step_map(9);
+    let finish = main_path.last().unwrap();
+    let result = model.get_reachability(&map, &main_path[0], finish, Some(&main_path));
+    assert!(result.finish_reachable);
+    assert!(result.unreachable_sections.is_empty());
+
+    let (map, main_path) = get_step_map(10);
+    let finish = main_path.last().unwrap();
+    let result = model.get_reachability(&map, &main_path[0], finish, Some(&main_path));
+    assert!(!result.finish_reachable);
+    assert_eq!(
+        result.unreachable_sections,
+        vec![(Position::new(22, 7), finish.clone())]
+    );
+}
+
+#[test]
+fn hook_only_gap() {
+    let (map, main_path) = get_gap_map();
+    let finish = main_path.last().unwrap();
+
+    let result =
+        MovementModel::default().get_reachability(&map, &main_path[0], finish, Some(&main_path));
+    assert!(result.finish_reachable);
+    assert!(result.unreachable_sections.is_empty());
+
+    let model = MovementModel {
+        hook_range: 0,
+        ..MovementModel::default()
+    };
+    let result = model.get_reachability(&map, &main_path[0], finish, Some(&main_path));
+    assert!(!result.finish_reachable);
+    assert_eq!(result.unreachable_sections.len(), 1);
+
+    let (start, end) = &result.unreachable_sections[0];
+    assert!(start.x > 11 && start.x < 39, "{:?}", start);
+    assert_eq!(end, finish);
+}
+
+#[test]
+fn freeze_blocks_path() {
+    let (mut map, main_path) = get_gap_map();
+    for x in 11..=39 {
+        map.grid[[x, 8]] = BlockType::Hookable;
+    }
+
+    // hooks pass through freeze, but the tee can't
+    for y in 3..=7 {
+        map.grid[[20, y]] = BlockType::Freeze;
+    }
+
+    let finish = main_path.last().unwrap();
+    let result =
+        MovementModel::default().get_reachability(&map, &main_path[0], finish, Some(&main_path));
+    assert!(!result.finish_reachable);
+    assert_eq!(
+        result.unreachable_sections,
+        vec![(Position::new(22, 7), finish.clone())]
+    );
+}
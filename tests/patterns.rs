@@ -0,0 +1,87 @@
+use gores_mapgen::{
+    map::{BlockType, Map},
+    patterns::{Pattern, PatternReplacement},
+    position::Position,
+};
+
+fn parse(rows: &[&str]) -> Pattern {
+    Pattern::parse(&rows.iter().map(|row| row.to_string()).collect::<Vec<_>>()).unwrap()
+}
+
+/// 7x7 map of alternating hookable, empty and freeze blocks, so that every change is visible
+fn get_mixed_map() -> Map {
+    let mut map = Map::new(7, 7, BlockType::Empty);
+    for ((x, y), block) in map.grid.indexed_iter_mut() {
+        *block = [BlockType::Hookable, BlockType::Empty, BlockType::Freeze][(x + 2 * y) % 3].clone();
+    }
+
+    map
+}
+
+#[test]
+fn pattern_variants_are_deduplicated() {
+    for (rows, variant_count) in [
+        (vec!["X X X", "X _ X", "X X X"], 1),
+        (vec!["X _ X"], 2),
+        (vec!["X X X", "_ _ _", "_ _ _"], 4),
+        (vec!["X X X", "X X _", "X _ _"], 4),
+        (vec!["X X _", "_ _ _", "_ _ _"], 8),
+    ] {
+        let pattern = parse(&rows);
+        let variants = pattern.variants();
+
+        assert_eq!(variants.len(), variant_count, "{:?}", rows);
+        assert_eq!(variants[0], pattern);
+        for (index, variant) in variants.iter().enumerate() {
+            assert!(!variants[index + 1..].contains(variant), "{:?}", rows);
+        }
+    }
+}
+
+#[test]
+fn center_replacements_only_change_center() {
+    let (top_left, bot_right, center) = (
+        Position::new(2, 2),
+        Position::new(4, 4),
+        Position::new(3, 3),
+    );
+
+    for (replacement, before, after) in [
+        (PatternReplacement::Keep, BlockType::Hookable, BlockType::Hookable),
+        (PatternReplacement::EmptyCenter, BlockType::Hookable, BlockType::Empty),
+        (PatternReplacement::EmptyCenter, BlockType::Freeze, BlockType::Empty),
+        (PatternReplacement::SolidCenter, BlockType::Empty, BlockType::Hookable),
+    ] {
+        let mut map = get_mixed_map();
+        map.grid[[center.x, center.y]] = before;
+        let original = map.grid.clone();
+
+        replacement.apply(&mut map, &top_left, &bot_right);
+
+        for ((x, y), block) in map.grid.indexed_iter() {
+            if (x, y) == (center.x, center.y) {
+                assert_eq!(block, &after, "{:?}", replacement);
+            } else {
+                assert_eq!(block, &original[[x, y]], "{:?} at {} {}", replacement, x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn remove_hookable_only_changes_matched_area() {
+    let (top_left, bot_right) = (Position::new(2, 2), Position::new(4, 4));
+    let mut map = get_mixed_map();
+    let original = map.grid.clone();
+
+    PatternReplacement::RemoveHookable.apply(&mut map, &top_left, &bot_right);
+
+    for ((x, y), block) in map.grid.indexed_iter() {
+        let inside = (2..=4).contains(&x) && (2..=4).contains(&y);
+        if inside && original[[x, y]] == BlockType::Hookable {
+            assert_eq!(block, &BlockType::Empty, "at {} {}", x, y);
+        } else {
+            assert_eq!(block, &original[[x, y]], "at {} {}", x, y);
+        }
+    }
+}